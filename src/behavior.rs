@@ -0,0 +1,266 @@
+use crate::{
+    chunk::Block,
+    entity::FallingBlock,
+    fluid,
+    noise::Random,
+    registry::Behavior,
+    world::{World, DIRECTIONS, DOWN, UP},
+};
+use std::collections::{HashSet, VecDeque};
+
+/// How many blocks in every loaded chunk get a random tick on each world tick,
+/// chunks are as tall as they are wide so each of them is a single section
+pub const RANDOM_TICKS_PER_CHUNK: u32 = 3;
+
+/// How many ticks it takes a falling block to fall one block
+pub const FALLING_TICK_DELAY: u32 = 2;
+
+/// The light level that has to be above a block for a [`Behavior::Spreading`] block to spread onto it
+const SPREAD_MIN_LIGHT: u8 = 9;
+
+/// A block that an update changes, and the fluid level it changes to
+pub type BlockChange = (cgmath::Vector3<i32>, Block, u8);
+
+/// What a scheduled update changes in the world, all of it has to happen together
+#[derive(Debug, Default)]
+pub struct Update {
+    pub changes: Vec<BlockChange>,
+    /// Blocks that start falling, the changes already remove them from their chunks
+    pub falling_blocks: Vec<FallingBlock>,
+}
+
+fn named(world: &World, name: &str) -> Block {
+    world
+        .registry()
+        .by_name(name)
+        .expect("the registry checks that behaviors refer to existing blocks")
+}
+
+/// How many ticks to wait before the scheduled update of a block after something next to it changed,
+/// `None` if nothing would happen in the update
+pub fn tick_delay(world: &World, position: cgmath::Vector3<i32>) -> Option<u32> {
+    let falling = world
+        .get_block(position)
+        .filter(|&block| world.registry().get(block).behavior == Behavior::Falling)
+        .map(|_| FALLING_TICK_DELAY);
+    falling
+        .into_iter()
+        .chain(fluid::tick_delay(world, position))
+        .min()
+}
+
+/// What a scheduled update of the block changes
+pub fn scheduled_update(world: &World, position: cgmath::Vector3<i32>) -> Update {
+    let Some(block) = world.get_block(position) else {
+        return Update::default();
+    };
+    let registry = world.registry();
+    if registry.get(block).behavior == Behavior::Falling {
+        let unsupported = world
+            .get_block(position + DOWN)
+            .is_some_and(|below| !registry.get(below).solid);
+        return if unsupported {
+            Update {
+                changes: vec![(position, Block::AIR, 0)],
+                falling_blocks: vec![FallingBlock::new(block, position)],
+            }
+        } else {
+            Update::default()
+        };
+    }
+    Update {
+        changes: fluid::next_state(world, position)
+            .map(|(block, level)| (position, block, level))
+            .into_iter()
+            .collect(),
+        falling_blocks: vec![],
+    }
+}
+
+/// What a random tick of the block changes, all the changes have to be applied together
+pub fn random_update(
+    world: &World,
+    position: cgmath::Vector3<i32>,
+    random: &mut Random,
+) -> Vec<BlockChange> {
+    let Some(block) = world.get_block(position) else {
+        return vec![];
+    };
+    match &world.registry().get(block).behavior {
+        Behavior::None | Behavior::Falling => vec![],
+
+        Behavior::Spreading { onto, covered } => {
+            if !is_uncovered(world, position) {
+                return vec![(position, named(world, covered), 0)];
+            }
+            // like grass, it can spread a few blocks down a slope but only one block up
+            let target = position
+                + cgmath::vec3(
+                    random.range(-1..=1),
+                    random.range(-3..=1),
+                    random.range(-1..=1),
+                );
+            let spreads = world.get_block(target) == Some(named(world, onto))
+                && is_uncovered(world, target)
+                && world
+                    .get_light(target + UP)
+                    .is_some_and(|light| light.sky.max(light.block) >= SPREAD_MIN_LIGHT);
+            if spreads {
+                vec![(target, block, 0)]
+            } else {
+                vec![]
+            }
+        }
+
+        Behavior::Growing { into, min_light } => {
+            let grows = world
+                .get_light(position)
+                .is_some_and(|light| light.sky.max(light.block) >= *min_light);
+            if grows {
+                vec![(position, named(world, into), 0)]
+            } else {
+                vec![]
+            }
+        }
+
+        Behavior::Decaying {
+            sustained_by,
+            distance,
+        } => {
+            if is_sustained(world, position, named(world, sustained_by), *distance) {
+                vec![]
+            } else {
+                vec![(position, Block::AIR, 0)]
+            }
+        }
+    }
+}
+
+/// Whether the block above is transparent, blocks at the top of the loaded chunks are uncovered
+fn is_uncovered(world: &World, position: cgmath::Vector3<i32>) -> bool {
+    world
+        .get_block(position + UP)
+        .is_none_or(|above| world.registry().get(above).transparent)
+}
+
+/// Searches through the connected blocks of the same kind for a `sustained_by` block that is at most `distance` steps away
+fn is_sustained(
+    world: &World,
+    position: cgmath::Vector3<i32>,
+    sustained_by: Block,
+    distance: u8,
+) -> bool {
+    let block = world.get_block(position);
+    let mut visited = HashSet::from([position]);
+    let mut queue = VecDeque::from([(position, 0)]);
+    while let Some((position, steps)) = queue.pop_front() {
+        if steps == distance {
+            continue;
+        }
+        for direction in DIRECTIONS {
+            let neighbor = position + direction;
+            if !visited.insert(neighbor) {
+                continue;
+            }
+            match world.get_block(neighbor) {
+                Some(neighbor_block) if neighbor_block == sustained_by => return true,
+                // blocks next to unloaded chunks are kept until the chunk is loaded
+                None => return true,
+                neighbor_block if neighbor_block == block => queue.push_back((neighbor, steps + 1)),
+                _ => {}
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs random ticks on a block until one of them changes something
+    fn random_ticks_until_change(world: &mut World, position: cgmath::Vector3<i32>) -> bool {
+        let mut random = Random::new(1);
+        for _ in 0..1000 {
+            let changes = random_update(world, position, &mut random);
+            if !changes.is_empty() {
+                for (position, block, _) in changes {
+                    world.set_block(position, block);
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn grass_spreads_onto_lit_dirt_and_dies_when_covered() {
+        let mut world = World::test_floor("dirt");
+        let registry = world.registry();
+        let (dirt, grass, stone) = (
+            registry.by_name("dirt").unwrap(),
+            registry.by_name("grass").unwrap(),
+            registry.by_name("stone").unwrap(),
+        );
+        let position = cgmath::vec3(5, 0, 5);
+        world.set_block(position, grass);
+        assert!(random_ticks_until_change(&mut world, position));
+        assert_eq!(world.get_block(position), Some(grass));
+        let spread = (4..7)
+            .flat_map(|x| (4..7).map(move |z| cgmath::vec3(x, 0, z)))
+            .filter(|&position| world.get_block(position) == Some(grass))
+            .count();
+        assert_eq!(spread, 2);
+
+        world.set_block(position + UP, stone);
+        assert!(random_ticks_until_change(&mut world, position));
+        assert_eq!(world.get_block(position), Some(dirt));
+    }
+
+    #[test]
+    fn crops_grow_in_the_light() {
+        let mut world = World::test_floor("dirt");
+        let registry = world.registry();
+        let (wheat_0, wheat_1, stone) = (
+            registry.by_name("wheat_0").unwrap(),
+            registry.by_name("wheat_1").unwrap(),
+            registry.by_name("stone").unwrap(),
+        );
+        let lit = cgmath::vec3(2, 1, 2);
+        let dark = cgmath::vec3(8, 1, 8);
+        world.set_block(lit, wheat_0);
+        world.set_block(dark, wheat_0);
+        for direction in DIRECTIONS {
+            if direction != DOWN {
+                world.set_block(dark + direction, stone);
+            }
+        }
+
+        assert!(random_ticks_until_change(&mut world, lit));
+        assert_eq!(world.get_block(lit), Some(wheat_1));
+        assert!(!random_ticks_until_change(&mut world, dark));
+    }
+
+    #[test]
+    fn leaves_decay_away_from_logs() {
+        let mut world = World::test_floor("stone");
+        let registry = world.registry();
+        let (log, leaves) = (
+            registry.by_name("log").unwrap(),
+            registry.by_name("leaves").unwrap(),
+        );
+        world.set_block(cgmath::vec3(2, 1, 2), log);
+        for x in 3..10 {
+            world.set_block(cgmath::vec3(x, 1, 2), leaves);
+        }
+
+        assert!(!random_ticks_until_change(
+            &mut world,
+            cgmath::vec3(6, 1, 2)
+        ));
+        assert!(random_ticks_until_change(&mut world, cgmath::vec3(7, 1, 2)));
+        assert_eq!(world.get_block(cgmath::vec3(7, 1, 2)), Some(Block::AIR));
+        // the leaves further away decay too
+        assert!(random_ticks_until_change(&mut world, cgmath::vec3(8, 1, 2)));
+    }
+}
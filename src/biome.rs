@@ -0,0 +1,88 @@
+use crate::{chunk::Block, generation::GenerationBlocks};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    Tundra,
+}
+
+impl Biome {
+    /// Every biome, indexed by [`Biome::id`]
+    pub const ALL: [Biome; 4] = [Biome::Plains, Biome::Forest, Biome::Desert, Biome::Tundra];
+
+    /// The id used to store the biome on disk
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.get(id as usize).copied()
+    }
+
+    /// The `(temperature, humidity)` that this biome is centered on, both are in the range `-1.0..=1.0`
+    pub fn climate(self) -> (f64, f64) {
+        match self {
+            Biome::Plains => (0.1, -0.1),
+            Biome::Forest => (0.2, 0.6),
+            Biome::Desert => (0.7, -0.6),
+            Biome::Tundra => (-0.7, 0.1),
+        }
+    }
+
+    /// The block on the top of each column
+    pub fn surface_block(self, blocks: &GenerationBlocks) -> Block {
+        match self {
+            Biome::Plains | Biome::Forest => blocks.grass,
+            Biome::Desert => blocks.sand,
+            Biome::Tundra => blocks.snow,
+        }
+    }
+
+    /// The block between the surface block and the stone
+    pub fn filler_block(self, blocks: &GenerationBlocks) -> Block {
+        match self {
+            Biome::Plains | Biome::Forest | Biome::Tundra => blocks.dirt,
+            Biome::Desert => blocks.sand,
+        }
+    }
+
+    /// How many blocks the terrain can rise above or sink below the base height
+    pub fn height_scale(self) -> f64 {
+        match self {
+            Biome::Plains => 8.0,
+            Biome::Forest => 20.0,
+            Biome::Desert => 6.0,
+            Biome::Tundra => 32.0,
+        }
+    }
+
+    /// The chance for each column to have a decoration placed on it
+    pub fn decoration_density(self) -> f64 {
+        match self {
+            Biome::Plains => 0.002,
+            Biome::Forest => 0.03,
+            Biome::Desert => 0.001,
+            Biome::Tundra => 0.004,
+        }
+    }
+
+    pub fn grass_color(self) -> cgmath::Vector3<f32> {
+        match self {
+            Biome::Plains => cgmath::vec3(0.3, 0.65, 0.15),
+            Biome::Forest => cgmath::vec3(0.15, 0.5, 0.1),
+            Biome::Desert => cgmath::vec3(0.6, 0.6, 0.25),
+            Biome::Tundra => cgmath::vec3(0.45, 0.6, 0.5),
+        }
+    }
+
+    pub fn foliage_color(self) -> cgmath::Vector3<f32> {
+        match self {
+            Biome::Plains => cgmath::vec3(0.25, 0.55, 0.1),
+            Biome::Forest => cgmath::vec3(0.1, 0.4, 0.05),
+            Biome::Desert => cgmath::vec3(0.5, 0.5, 0.2),
+            Biome::Tundra => cgmath::vec3(0.3, 0.45, 0.4),
+        }
+    }
+}
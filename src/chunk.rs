@@ -1,80 +1,580 @@
-#[derive(Clone, Copy)]
-pub enum Block {
-    Air,
-    Stone,
+use crate::{
+    biome::Biome,
+    fluid,
+    light::{Light, MAX_LIGHT},
+    model::{self, ModelBox, SideCoverage},
+    palette::PaletteStorage,
+    registry::{BlockRegistry, Model},
+    state::{BlockState, Direction},
+};
+
+pub const CHUNK_SIZE: usize = 16;
+
+/// The id of a block in the [`BlockRegistry`](crate::registry::BlockRegistry), this is what chunks store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Block(pub u16);
+
+impl Block {
+    pub const AIR: Block = Block(0);
+}
+
+/// A rectangle of block faces that all face the same direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quad {
+    /// The block with the smallest coordinates that the quad covers
+    pub position: cgmath::Vector3<u8>,
+    /// How many blocks the quad covers along each axis, this is always 1 along the axis of the face normal
+    pub size: cgmath::Vector3<u8>,
+    pub block: Block,
+    pub state: BlockState,
+    /// The light of the block in front of the face
+    pub light: Light,
+    /// How much the corners of the face are occluded by the blocks around them, from 0 (fully occluded)
+    /// to 3 (not occluded). The corners are indexed by `u + 2 * v`, where `u` and `v` are 0 or 1 along
+    /// the two axes of the face plane given by [`plane_axes`]
+    pub ambient_occlusion: [u8; 4],
+    /// The box of the block model that the face is on, the face is on its side that points along the normal.
+    /// Quads that cover more than one block have the same box in every block
+    pub bounds: ModelBox,
+}
+
+/// The two axes that lie in the plane of a face whose normal is along `normal_axis`, in increasing order
+pub fn plane_axes(normal_axis: usize) -> (usize, usize) {
+    match normal_axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        2 => (0, 1),
+        _ => unreachable!(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshingMode {
+    /// One quad for every visible block face
+    Naive,
+    /// Merges neighboring coplanar faces of the same block into as few quads as possible
+    Greedy,
 }
 
-#[derive(Default)]
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct Faces {
-    pub front: Vec<(cgmath::Vector3<u8>, Block)>,
-    pub back: Vec<(cgmath::Vector3<u8>, Block)>,
-    pub left: Vec<(cgmath::Vector3<u8>, Block)>,
-    pub right: Vec<(cgmath::Vector3<u8>, Block)>,
-    pub top: Vec<(cgmath::Vector3<u8>, Block)>,
-    pub bottom: Vec<(cgmath::Vector3<u8>, Block)>,
+    pub front: Vec<Quad>,
+    pub back: Vec<Quad>,
+    pub left: Vec<Quad>,
+    pub right: Vec<Quad>,
+    pub top: Vec<Quad>,
+    pub bottom: Vec<Quad>,
+    /// Blocks with a [`Model::Cross`], each of these is drawn as two planes that can be seen from both sides
+    pub cross: Vec<Quad>,
 }
 
+#[derive(Clone)]
 pub struct Chunk {
-    pub blocks: Box<[[[Block; 16]; 16]; 16]>,
+    /// Use [`Chunk::get_block`] and [`Chunk::set_block`] to access blocks by their position
+    pub blocks: PaletteStorage<Block>,
+    /// The biome of each column, indexed by `[x][z]`
+    pub biomes: [[Biome; CHUNK_SIZE]; CHUNK_SIZE],
+    /// Packed with [`Light::pack`], this is calculated by the [`light`](crate::light) module
+    /// and is not saved with the chunk
+    pub light: PaletteStorage<u8>,
+    /// The level of the fluid in each block, see [`fluid::SOURCE_LEVEL`]. Blocks that aren't fluids are at level 0
+    pub fluid_levels: PaletteStorage<u8>,
+    /// The state of each block, see [`BlockState`]. Blocks without any state are at the default state
+    pub states: PaletteStorage<BlockState>,
 }
 
 impl Chunk {
+    pub fn new() -> Self {
+        Self {
+            blocks: PaletteStorage::new(Block::AIR),
+            biomes: [[Biome::Plains; CHUNK_SIZE]; CHUNK_SIZE],
+            light: PaletteStorage::new(Light::default().pack()),
+            fluid_levels: PaletteStorage::new(0),
+            states: PaletteStorage::new(BlockState::default()),
+        }
+    }
+
+    /// The index of a block in [`Chunk::blocks`], [`Chunk::light`], [`Chunk::fluid_levels`] and [`Chunk::states`]
+    pub(crate) fn index(x: u8, y: u8, z: u8) -> usize {
+        (x as usize * CHUNK_SIZE + y as usize) * CHUNK_SIZE + z as usize
+    }
+
     pub fn get_block(&self, x: u8, y: u8, z: u8) -> Option<Block> {
-        self.blocks
-            .get(x as usize)
-            .and_then(|blocks| blocks.get(y as usize))
-            .and_then(|blocks| blocks.get(z as usize))
-            .copied()
+        let size = CHUNK_SIZE as u8;
+        (x < size && y < size && z < size).then(|| self.blocks.get(Self::index(x, y, z)))
+    }
+
+    pub fn get_light(&self, x: u8, y: u8, z: u8) -> Option<Light> {
+        let size = CHUNK_SIZE as u8;
+        (x < size && y < size && z < size)
+            .then(|| Light::unpack(self.light.get(Self::index(x, y, z))))
+    }
+
+    pub fn set_light(&mut self, x: u8, y: u8, z: u8, light: Light) {
+        assert!(
+            (x as usize) < CHUNK_SIZE && (y as usize) < CHUNK_SIZE && (z as usize) < CHUNK_SIZE
+        );
+        debug_assert!(light.sky <= MAX_LIGHT && light.block <= MAX_LIGHT);
+        self.light.set(Self::index(x, y, z), light.pack());
+    }
+
+    pub fn get_fluid_level(&self, x: u8, y: u8, z: u8) -> Option<u8> {
+        let size = CHUNK_SIZE as u8;
+        (x < size && y < size && z < size).then(|| self.fluid_levels.get(Self::index(x, y, z)))
+    }
+
+    pub fn set_fluid_level(&mut self, x: u8, y: u8, z: u8, level: u8) {
+        assert!(
+            (x as usize) < CHUNK_SIZE && (y as usize) < CHUNK_SIZE && (z as usize) < CHUNK_SIZE
+        );
+        self.fluid_levels.set(Self::index(x, y, z), level);
+    }
+
+    pub fn get_state(&self, x: u8, y: u8, z: u8) -> Option<BlockState> {
+        let size = CHUNK_SIZE as u8;
+        (x < size && y < size && z < size).then(|| self.states.get(Self::index(x, y, z)))
+    }
+
+    pub fn set_state(&mut self, x: u8, y: u8, z: u8, state: BlockState) {
+        assert!(
+            (x as usize) < CHUNK_SIZE && (y as usize) < CHUNK_SIZE && (z as usize) < CHUNK_SIZE
+        );
+        self.states.set(Self::index(x, y, z), state);
+    }
+
+    pub fn biome(&self, x: u8, z: u8) -> Biome {
+        self.biomes[x as usize][z as usize]
+    }
+
+    pub fn set_block(&mut self, x: u8, y: u8, z: u8, block: Block) {
+        assert!(
+            (x as usize) < CHUNK_SIZE && (y as usize) < CHUNK_SIZE && (z as usize) < CHUNK_SIZE
+        );
+        self.blocks.set(Self::index(x, y, z), block);
+    }
+
+    /// Shrinks the block storage after a lot of edits, see [`PaletteStorage::compact`]
+    pub fn compact(&mut self) {
+        self.blocks.compact();
+        self.fluid_levels.compact();
+        self.states.compact();
     }
 
-    pub fn generate_faces(&self) -> Faces {
+    /// `get_neighbor` is called with chunk local coordinates that are outside of this chunk and returns the block,
+    /// its state, its light and its fluid level. Returning `None` means the block is not loaded and will be
+    /// treated as air lit by the sky.
+    ///
+    /// A face on the side of a block is hidden if the model of the block in front of it covers all of it,
+    /// as long as that block isn't transparent or is the same block so the inside of a volume of glass doesn't
+    /// have any faces. Faces inside of the block, like the top of a slab, are always visible
+    pub fn generate_faces(
+        &self,
+        registry: &BlockRegistry,
+        meshing_mode: MeshingMode,
+        mut get_neighbor: impl FnMut(cgmath::Vector3<i32>) -> Option<(Block, BlockState, Light, u8)>,
+    ) -> Faces {
+        let mut get = |position: cgmath::Vector3<i32>| {
+            if (0..CHUNK_SIZE as i32).contains(&position.x)
+                && (0..CHUNK_SIZE as i32).contains(&position.y)
+                && (0..CHUNK_SIZE as i32).contains(&position.z)
+            {
+                let index = Self::index(position.x as u8, position.y as u8, position.z as u8);
+                Some((
+                    self.blocks.get(index),
+                    self.states.get(index),
+                    Light::unpack(self.light.get(index)),
+                    self.fluid_levels.get(index),
+                ))
+            } else {
+                get_neighbor(position)
+            }
+        };
+
         let mut faces = Faces::default();
-        for x in 0u8..16 {
-            for y in 0u8..16 {
-                for z in 0u8..16 {
+        for x in 0u8..CHUNK_SIZE as u8 {
+            for y in 0u8..CHUNK_SIZE as u8 {
+                for z in 0u8..CHUNK_SIZE as u8 {
                     let position = cgmath::vec3(x, y, z);
-                    let block = self.blocks[x as usize][y as usize][z as usize];
-                    if !matches!(block, Block::Air) {
-                        if x.checked_add(1)
-                            .and_then(|x| self.get_block(x, y, z))
-                            .map_or(true, |block| matches!(block, Block::Air))
-                        {
-                            faces.front.push((position, block));
-                        }
-                        if x.checked_sub(1)
-                            .and_then(|x| self.get_block(x, y, z))
-                            .map_or(true, |block| matches!(block, Block::Air))
-                        {
-                            faces.back.push((position, block));
-                        }
-                        if y.checked_add(1)
-                            .and_then(|y| self.get_block(x, y, z))
-                            .map_or(true, |block| matches!(block, Block::Air))
-                        {
-                            faces.top.push((position, block));
-                        }
-                        if y.checked_sub(1)
-                            .and_then(|y| self.get_block(x, y, z))
-                            .map_or(true, |block| matches!(block, Block::Air))
-                        {
-                            faces.bottom.push((position, block));
-                        }
-                        if z.checked_add(1)
-                            .and_then(|z| self.get_block(x, y, z))
-                            .map_or(true, |block| matches!(block, Block::Air))
-                        {
-                            faces.right.push((position, block));
-                        }
-                        if z.checked_sub(1)
-                            .and_then(|z| self.get_block(x, y, z))
-                            .map_or(true, |block| matches!(block, Block::Air))
-                        {
-                            faces.left.push((position, block));
+                    let index = Self::index(x, y, z);
+                    let block = self.blocks.get(index);
+                    if block == Block::AIR {
+                        continue;
+                    }
+                    let definition = registry.get(block);
+                    let state = self.states.get(index);
+                    if definition.model == Model::Cross {
+                        faces.cross.push(Quad {
+                            position,
+                            size: cgmath::vec3(1, 1, 1),
+                            block,
+                            state,
+                            light: Light::unpack(self.light.get(index)),
+                            ambient_occlusion: [3; 4],
+                            bounds: ModelBox::FULL,
+                        });
+                        continue;
+                    }
+
+                    let is_fluid = registry.fluid(block).is_some();
+                    // the surface of fluid of the same kind as this block, given the block above the fluid
+                    let fluid_surface =
+                        |level: u8, above: Option<(Block, BlockState, Light, u8)>| {
+                            fluid::surface_height(
+                                level,
+                                above.is_some_and(|(above, ..)| above == block),
+                            )
+                        };
+                    let mut model_boxes = model::boxes(definition.model, state);
+                    if is_fluid {
+                        let above = get(position.cast::<i32>().unwrap() + cgmath::vec3(0, 1, 0));
+                        model_boxes[0].max.y = fluid_surface(self.fluid_levels.get(index), above);
+                    }
+                    for model_box in model_boxes {
+                        for (quads, direction) in [
+                            (&mut faces.front, Direction::PositiveX),
+                            (&mut faces.back, Direction::NegativeX),
+                            (&mut faces.top, Direction::PositiveY),
+                            (&mut faces.bottom, Direction::NegativeY),
+                            (&mut faces.right, Direction::PositiveZ),
+                            (&mut faces.left, Direction::NegativeZ),
+                        ] {
+                            let front = position.cast::<i32>().unwrap() + direction.offset();
+                            let mut bounds = model_box;
+                            let light = match get(front) {
+                                None => Light::UNLOADED,
+                                Some((neighbor, neighbor_state, light, neighbor_level)) => {
+                                    let neighbor_definition = registry.get(neighbor);
+                                    let hidden =
+                                        if is_fluid && neighbor == block && direction.axis() != 1 {
+                                            // next to lower fluid of the same kind, the side shows above its surface
+                                            let above = get(front + cgmath::vec3(0, 1, 0));
+                                            bounds.min.y = fluid_surface(neighbor_level, above);
+                                            bounds.min.y >= bounds.max.y
+                                        } else {
+                                            model_box.touches_side(direction)
+                                                && (neighbor == block
+                                                    || !neighbor_definition.transparent)
+                                                && SideCoverage::of(
+                                                    neighbor_definition.model,
+                                                    neighbor_state,
+                                                    direction.opposite(),
+                                                )
+                                                .covers(model_box, direction.axis())
+                                        };
+                                    if hidden {
+                                        continue;
+                                    }
+                                    light
+                                }
+                            };
+
+                            let (u_axis, v_axis) = plane_axes(direction.axis());
+                            let mut is_occluder = |du: i32, dv: i32| {
+                                let mut position = front;
+                                position[u_axis] += du;
+                                position[v_axis] += dv;
+                                get(position)
+                                    .is_some_and(|(block, ..)| !registry.get(block).transparent)
+                            };
+                            let ambient_occlusion =
+                                [(-1, -1), (1, -1), (-1, 1), (1, 1)].map(|(du, dv)| {
+                                    let side_u = is_occluder(du, 0);
+                                    let side_v = is_occluder(0, dv);
+                                    // the corner block can't be seen when both sides are occluded
+                                    if side_u && side_v {
+                                        0
+                                    } else {
+                                        3 - side_u as u8 - side_v as u8 - is_occluder(du, dv) as u8
+                                    }
+                                });
+
+                            quads.push(Quad {
+                                position,
+                                size: cgmath::vec3(1, 1, 1),
+                                block,
+                                state,
+                                light,
+                                ambient_occlusion,
+                                bounds,
+                            });
                         }
                     }
                 }
             }
         }
+
+        if meshing_mode == MeshingMode::Greedy {
+            for (quads, normal_axis) in [
+                (&mut faces.front, 0),
+                (&mut faces.back, 0),
+                (&mut faces.top, 1),
+                (&mut faces.bottom, 1),
+                (&mut faces.left, 2),
+                (&mut faces.right, 2),
+            ] {
+                *quads = self.merge_quads(quads, normal_axis);
+            }
+        }
         faces
     }
+
+    /// Greedily merges single block quads that all face along `normal_axis` into rectangles.
+    /// Quads are only merged if they have the same block, state, biome, light, ambient occlusion and model box
+    /// so they look the same when rendered, and only along the axes that the model box fills
+    fn merge_quads(&self, quads: &[Quad], normal_axis: usize) -> Vec<Quad> {
+        let (u_axis, v_axis) = plane_axes(normal_axis);
+        let position = |layer: usize, u: usize, v: usize| {
+            let mut position = cgmath::vec3(0u8, 0, 0);
+            position[normal_axis] = layer as u8;
+            position[u_axis] = u as u8;
+            position[v_axis] = v as u8;
+            position
+        };
+
+        let mut merged = vec![];
+        // every visible face in the chunk, indexed by `[layer][u][v]`
+        let mut grid = [[[None; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
+        for quad in quads {
+            let p = quad.position;
+            let cell = &mut grid[p[normal_axis] as usize][p[u_axis] as usize][p[v_axis] as usize];
+            // models made of more than one box can have more than one face in the same direction
+            if cell.is_some() {
+                merged.push(*quad);
+                continue;
+            }
+            *cell = Some((
+                quad.block,
+                quad.state,
+                self.biome(p.x, p.z),
+                quad.light,
+                quad.ambient_occlusion,
+                quad.bounds,
+            ));
+        }
+
+        for (layer, cells) in grid.iter_mut().enumerate() {
+            for u in 0..CHUNK_SIZE {
+                for v in 0..CHUNK_SIZE {
+                    let Some(key) = cells[u][v] else {
+                        continue;
+                    };
+
+                    let bounds = key.5;
+                    let mut height = 1;
+                    while bounds.is_full_along(v_axis)
+                        && v + height < CHUNK_SIZE
+                        && cells[u][v + height] == Some(key)
+                    {
+                        height += 1;
+                    }
+                    let mut width = 1;
+                    while bounds.is_full_along(u_axis)
+                        && u + width < CHUNK_SIZE
+                        && cells[u + width][v..v + height]
+                            .iter()
+                            .all(|&cell| cell == Some(key))
+                    {
+                        width += 1;
+                    }
+
+                    for row in &mut cells[u..u + width] {
+                        row[v..v + height].fill(None);
+                    }
+
+                    let mut size = cgmath::vec3(1, 1, 1);
+                    size[u_axis] = width as u8;
+                    size[v_axis] = height as u8;
+                    merged.push(Quad {
+                        position: position(layer, u, v),
+                        size,
+                        block: key.0,
+                        state: key.1,
+                        light: key.3,
+                        ambient_occlusion: key.4,
+                        bounds,
+                    });
+                }
+            }
+        }
+        merged
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::default_registry;
+
+    fn area(quads: &[Quad]) -> usize {
+        quads
+            .iter()
+            .map(|quad| quad.size.x as usize * quad.size.y as usize * quad.size.z as usize)
+            .sum()
+    }
+
+    #[test]
+    fn greedy_meshing_covers_the_same_faces() {
+        let registry = default_registry();
+        let stone = registry.by_name("stone").unwrap();
+        let dirt = registry.by_name("dirt").unwrap();
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE as u8 {
+            for z in 0..CHUNK_SIZE as u8 {
+                chunk.set_block(x, 0, z, stone);
+            }
+        }
+        chunk.set_block(3, 1, 4, dirt);
+        chunk.set_block(4, 1, 4, dirt);
+
+        let naive = chunk.generate_faces(&registry, MeshingMode::Naive, |_| None);
+        let greedy = chunk.generate_faces(&registry, MeshingMode::Greedy, |_| None);
+        for (naive, greedy) in [
+            (&naive.front, &greedy.front),
+            (&naive.back, &greedy.back),
+            (&naive.left, &greedy.left),
+            (&naive.right, &greedy.right),
+            (&naive.top, &greedy.top),
+            (&naive.bottom, &greedy.bottom),
+        ] {
+            assert_eq!(area(naive), area(greedy));
+        }
+
+        // the dirt blocks and their ambient occlusion split up the top of the floor,
+        // but its bottom is one big quad
+        assert_eq!(naive.bottom.len(), CHUNK_SIZE * CHUNK_SIZE);
+        assert_eq!(greedy.bottom.len(), 1);
+        assert!(greedy.top.len() > 1 && greedy.top.len() < 20);
+        assert!(greedy
+            .top
+            .iter()
+            .any(|quad| quad.block == dirt && quad.size == cgmath::vec3(2, 1, 1)));
+    }
+
+    #[test]
+    fn faces_next_to_transparent_blocks_are_visible() {
+        let registry = default_registry();
+        let stone = registry.by_name("stone").unwrap();
+        let glass = registry.by_name("glass").unwrap();
+        let mut chunk = Chunk::new();
+        chunk.set_block(4, 4, 4, stone);
+        chunk.set_block(5, 4, 4, glass);
+        chunk.set_block(6, 4, 4, glass);
+        chunk.set_block(7, 4, 4, stone);
+
+        let faces = chunk.generate_faces(&registry, MeshingMode::Naive, |_| None);
+        let has_face = |quads: &[Quad], x: u8| quads.iter().any(|quad| quad.position.x == x);
+        // the stone is visible through the glass on both sides
+        assert!(has_face(&faces.front, 4));
+        assert!(has_face(&faces.back, 7));
+        // but there are no faces between the two glass blocks
+        assert!(!has_face(&faces.front, 5));
+        assert!(!has_face(&faces.back, 6));
+        // glass is still hidden by opaque blocks
+        assert!(!has_face(&faces.back, 5));
+        assert!(!has_face(&faces.front, 6));
+        assert!(has_face(&faces.top, 5) && has_face(&faces.top, 6));
+    }
+
+    #[test]
+    fn partial_models_only_hide_what_they_cover() {
+        let registry = default_registry();
+        let stone = registry.by_name("stone").unwrap();
+        let slab = registry.by_name("stone_slab").unwrap();
+        let wheat = registry.by_name("wheat_3").unwrap();
+        let mut chunk = Chunk::new();
+        chunk.set_block(4, 4, 4, stone);
+        chunk.set_block(5, 4, 4, slab);
+        chunk.set_block(4, 5, 4, slab);
+        chunk.set_block(4, 3, 4, wheat);
+        for x in 6..10 {
+            chunk.set_block(x, 4, 8, slab);
+        }
+
+        let faces = chunk.generate_faces(&registry, MeshingMode::Greedy, |_| None);
+        let find = |quads: &[Quad], position: cgmath::Vector3<u8>| {
+            quads
+                .iter()
+                .filter(|quad| quad.position == position)
+                .map(|quad| quad.bounds)
+                .collect::<Vec<_>>()
+        };
+        // the slab next to the stone only covers the bottom half of its side
+        assert_eq!(find(&faces.front, cgmath::vec3(4, 4, 4)), [ModelBox::FULL]);
+        assert!(find(&faces.back, cgmath::vec3(5, 4, 4)).is_empty());
+        // but the slab on top covers all of it, and the top of the slab is inside of the block
+        assert!(find(&faces.top, cgmath::vec3(4, 4, 4)).is_empty());
+        let slab_box = ModelBox::against(Direction::NegativeY, 8);
+        assert_eq!(find(&faces.top, cgmath::vec3(4, 5, 4)), [slab_box]);
+        // plants don't hide anything
+        assert_eq!(find(&faces.bottom, cgmath::vec3(4, 4, 4)), [ModelBox::FULL]);
+        assert_eq!(find(&faces.cross, cgmath::vec3(4, 3, 4)).len(), 1);
+        // the tops of slabs next to each other are merged, and the sides are only merged horizontally
+        assert!(faces.top.iter().any(
+            |quad| quad.position == cgmath::vec3(6, 4, 8) && quad.size == cgmath::vec3(4, 1, 1)
+        ));
+        assert!(faces.right.iter().all(|quad| quad.size.y == 1));
+    }
+
+    #[test]
+    fn higher_fluid_shows_its_side_above_lower_fluid() {
+        let registry = default_registry();
+        let water = registry.by_name("water").unwrap();
+        let mut chunk = Chunk::new();
+        for (x, level) in [(4, fluid::SOURCE_LEVEL), (5, fluid::SOURCE_LEVEL / 2)] {
+            chunk.set_block(x, 4, 4, water);
+            chunk.set_fluid_level(x, 4, 4, level);
+        }
+
+        let faces = chunk.generate_faces(&registry, MeshingMode::Naive, |_| None);
+        let sides = |quads: &[Quad], x: u8| {
+            quads
+                .iter()
+                .filter(|quad| quad.position == cgmath::vec3(x, 4, 4))
+                .map(|quad| quad.bounds)
+                .collect::<Vec<_>>()
+        };
+        let mut exposed = ModelBox::FULL;
+        exposed.min.y = fluid::surface_height(fluid::SOURCE_LEVEL / 2, false);
+        exposed.max.y = fluid::surface_height(fluid::SOURCE_LEVEL, false);
+        assert_eq!(sides(&faces.front, 4), [exposed]);
+        assert!(sides(&faces.back, 5).is_empty());
+    }
+
+    #[test]
+    fn corners_next_to_blocks_are_occluded() {
+        let registry = default_registry();
+        let stone = registry.by_name("stone").unwrap();
+        let mut chunk = Chunk::new();
+        for x in 0..4 {
+            for z in 0..4 {
+                chunk.set_block(x, 0, z, stone);
+            }
+        }
+        // a wall along the x axis behind the floor blocks at z = 1, and a block next to (1, 0, 1)
+        for x in 0..4 {
+            chunk.set_block(x, 1, 0, stone);
+        }
+        chunk.set_block(2, 1, 1, stone);
+
+        let faces = chunk.generate_faces(&registry, MeshingMode::Naive, |_| None);
+        let top = |x: u8, z: u8| {
+            faces
+                .top
+                .iter()
+                .find(|quad| quad.position == cgmath::vec3(x, 0, z))
+                .unwrap()
+                .ambient_occlusion
+        };
+        // the corners are indexed by x + 2 * z
+        assert_eq!(top(0, 3), [3, 3, 3, 3]);
+        assert_eq!(top(0, 1), [2, 1, 3, 3]);
+        // both sides of the corner between the wall and the block are occluded
+        assert_eq!(top(1, 1), [1, 0, 3, 2]);
+    }
 }
@@ -0,0 +1,136 @@
+use crate::{chunk::Block, world::World};
+
+/// How much faster a falling block falls every tick, in blocks per tick
+pub const GRAVITY: f32 = 0.04;
+/// The fastest a falling block can fall, in blocks per tick
+pub const MAX_FALL_SPEED: f32 = 2.0;
+
+/// A block with the [`Falling`](crate::registry::Behavior::Falling) behavior that lost its support.
+/// It isn't part of any chunk while it falls, and turns back into a block when it lands
+#[derive(Debug, Clone, PartialEq)]
+pub struct FallingBlock {
+    pub block: Block,
+    /// The block position that the falling block would be at if it didn't have to line up with the grid,
+    /// only `y` changes while it falls
+    pub position: cgmath::Vector3<f32>,
+    /// The position on the tick before, rendering interpolates between the two
+    pub previous_position: cgmath::Vector3<f32>,
+    /// In blocks per tick, this is negative when it moves down
+    pub velocity: f32,
+}
+
+/// What happened to a falling block on a tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fall {
+    Falling,
+    /// It hit the ground and has to be placed at this position
+    Landed(cgmath::Vector3<i32>),
+}
+
+impl FallingBlock {
+    pub fn new(block: Block, position: cgmath::Vector3<i32>) -> Self {
+        let position = position.cast().unwrap();
+        Self {
+            block,
+            position,
+            previous_position: position,
+            velocity: 0.0,
+        }
+    }
+
+    /// Where to draw the block, `alpha` is how far the world is into the next tick from `0.0` to `1.0`
+    pub fn render_position(&self, alpha: f32) -> cgmath::Vector3<f32> {
+        self.previous_position + (self.position - self.previous_position) * alpha
+    }
+
+    /// The block position that the center of the falling block is in
+    pub fn block_position(&self) -> cgmath::Vector3<i32> {
+        self.position.map(|x| x.round() as i32)
+    }
+
+    /// Moves the block down for one tick. It falls through blocks that aren't solid and lands on top of the
+    /// first solid block below it, it stops without landing at the edge of the loaded chunks
+    pub fn step(&mut self, world: &World) -> Fall {
+        self.previous_position = self.position;
+        self.velocity = (self.velocity - GRAVITY).max(-MAX_FALL_SPEED);
+        let target = self.position.y + self.velocity;
+
+        let cgmath::Vector3 { x, z, .. } = self.block_position();
+        // every block that the bottom of the falling block moves into on the way down
+        for y in (target.floor() as i32..self.position.y.floor() as i32).rev() {
+            match world.get_block(cgmath::vec3(x, y, z)) {
+                Some(block) if !world.registry().get(block).solid => {}
+                Some(_) => {
+                    self.position.y = (y + 1) as f32;
+                    return Fall::Landed(cgmath::vec3(x, y + 1, z));
+                }
+                None => {
+                    self.position.y = (y + 1) as f32;
+                    self.velocity = 0.0;
+                    return Fall::Falling;
+                }
+            }
+        }
+        self.position.y = target;
+        Fall::Falling
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_blocks_fall_and_land() {
+        let mut world = World::test_floor("stone");
+        let mut expected = World::test_floor("stone");
+        let registry = world.registry();
+        let (sand, gravel, stone) = (
+            registry.by_name("sand").unwrap(),
+            registry.by_name("gravel").unwrap(),
+            registry.by_name("stone").unwrap(),
+        );
+        world.set_block(cgmath::vec3(3, 9, 3), stone);
+        world.set_block(cgmath::vec3(3, 10, 3), sand);
+        world.set_block(cgmath::vec3(3, 11, 3), gravel);
+        world.run_ticks(10);
+        assert!(world.falling_blocks().is_empty());
+
+        world.set_block(cgmath::vec3(3, 9, 3), Block::AIR);
+        world.run_ticks(10);
+        assert!(!world.falling_blocks().is_empty());
+        for falling_block in world.falling_blocks() {
+            assert!(falling_block.position.y < falling_block.previous_position.y);
+        }
+
+        world.run_ticks(100);
+        assert!(world.falling_blocks().is_empty());
+        expected.set_block(cgmath::vec3(3, 1, 3), sand);
+        expected.set_block(cgmath::vec3(3, 2, 3), gravel);
+        assert!(
+            world.chunk(cgmath::vec3(0, 0, 0)).unwrap().blocks
+                == expected.chunk(cgmath::vec3(0, 0, 0)).unwrap().blocks
+        );
+    }
+
+    #[test]
+    fn falling_blocks_break_on_plants() {
+        let mut world = World::test_floor("stone");
+        let mut expected = World::test_floor("stone");
+        let registry = world.registry();
+        let (sand, wheat) = (
+            registry.by_name("sand").unwrap(),
+            registry.by_name("wheat_3").unwrap(),
+        );
+        world.set_block(cgmath::vec3(5, 1, 5), wheat);
+        world.set_block(cgmath::vec3(5, 12, 5), sand);
+        world.run_ticks(100);
+
+        assert!(world.falling_blocks().is_empty());
+        expected.set_block(cgmath::vec3(5, 1, 5), wheat);
+        assert!(
+            world.chunk(cgmath::vec3(0, 0, 0)).unwrap().blocks
+                == expected.chunk(cgmath::vec3(0, 0, 0)).unwrap().blocks
+        );
+    }
+}
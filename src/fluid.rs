@@ -0,0 +1,163 @@
+use crate::{
+    chunk::Block,
+    model::FULL_SIZE,
+    world::{World, DIRECTIONS, DOWN, UP},
+};
+
+/// The level of a fluid source, fluid that flows out of it has lower levels and dries up at 0
+pub const SOURCE_LEVEL: u8 = 8;
+
+/// The level of fluid that falls into the block below it, so it can still spread out after landing
+pub const FALLING_LEVEL: u8 = SOURCE_LEVEL - 1;
+
+/// How high the surface of a fluid block is drawn, in sixteenths of a block like [`FULL_SIZE`].
+/// Fluid with more of the same fluid above it fills the whole block
+pub fn surface_height(level: u8, fluid_above: bool) -> u8 {
+    if fluid_above {
+        FULL_SIZE
+    } else {
+        // even a source is a bit lower than a full block so the surface can be seen next to solid blocks
+        (level.min(SOURCE_LEVEL) as u32 * (FULL_SIZE as u32 - 2) / SOURCE_LEVEL as u32).max(1) as u8
+    }
+}
+
+/// How long it takes the fastest fluid in or next to the block to flow, `None` if there isn't any fluid
+pub fn tick_delay(world: &World, position: cgmath::Vector3<i32>) -> Option<u32> {
+    std::iter::once(position)
+        .chain(DIRECTIONS.map(|direction| position + direction))
+        .filter_map(|position| world.registry().fluid(world.get_block(position)?))
+        .map(|fluid| fluid.tick_delay)
+        .min()
+}
+
+/// What a block turns into on its next fluid update, returns `None` if it stays the same.
+///
+/// Fluid is pulled into a block from the blocks around it rather than pushed out of them, so the
+/// updates for a whole tick can be worked out before any of them are applied and the result doesn't
+/// depend on the order they run in. Only air and flowing fluid can be replaced, sources never dry up
+pub fn next_state(world: &World, position: cgmath::Vector3<i32>) -> Option<(Block, u8)> {
+    let registry = world.registry();
+    let block = world.get_block(position)?;
+    let level = world.fluid_level(position)?;
+
+    if registry.fluid(block).is_some() {
+        if let Some(hardened) = registry.hardens_into(block) {
+            let touches_other_fluid = DIRECTIONS.iter().any(|&direction| {
+                world
+                    .get_block(position + direction)
+                    .is_some_and(|neighbor| neighbor != block && registry.fluid(neighbor).is_some())
+            });
+            if touches_other_fluid {
+                return Some((hardened, 0));
+            }
+        }
+        if level >= SOURCE_LEVEL {
+            return None;
+        }
+    } else if block != Block::AIR {
+        return None;
+    }
+
+    // the fluid that would fill the block the highest wins
+    let mut next = (Block::AIR, 0);
+    if let Some(above) = world.get_block(position + UP) {
+        if registry.fluid(above).is_some() {
+            next = (above, FALLING_LEVEL);
+        }
+    }
+    for direction in DIRECTIONS {
+        if direction.y != 0 {
+            continue;
+        }
+        let neighbor_position = position + direction;
+        let Some(neighbor) = world.get_block(neighbor_position) else {
+            continue;
+        };
+        let Some(fluid) = registry.fluid(neighbor) else {
+            continue;
+        };
+        let neighbor_level = world.fluid_level(neighbor_position).unwrap();
+        // flowing fluid falls down before it spreads out sideways, an unloaded block stops it from falling
+        let falls = world
+            .get_block(neighbor_position + DOWN)
+            .is_some_and(|below| below == Block::AIR || below == neighbor);
+        if falls && neighbor_level < SOURCE_LEVEL {
+            continue;
+        }
+        let level = neighbor_level.saturating_sub(fluid.level_drop);
+        if level > next.1 {
+            next = (neighbor, level);
+        }
+    }
+
+    (next != (block, level)).then_some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+
+    fn fluid(world: &World, x: i32, y: i32, z: i32) -> (Block, u8) {
+        let position = cgmath::vec3(x, y, z);
+        (
+            world.get_block(position).unwrap(),
+            world.fluid_level(position).unwrap(),
+        )
+    }
+
+    #[test]
+    fn water_spreads_out_and_falls_into_holes() {
+        let mut world = World::test_floor("stone");
+        // an empty chunk below the floor for the water to fall into
+        world.insert_chunk(cgmath::vec3(0, -1, 0), Chunk::new());
+        let water = world.registry().by_name("water").unwrap();
+        let stone = world.registry().by_name("stone").unwrap();
+        world.set_block(cgmath::vec3(4, 0, 4), Block::AIR);
+        world.set_block(cgmath::vec3(4, 1, 8), water);
+        world.run_ticks(200);
+
+        assert_eq!(fluid(&world, 4, 1, 8), (water, SOURCE_LEVEL));
+        assert_eq!(fluid(&world, 5, 1, 8), (water, SOURCE_LEVEL - 1));
+        assert_eq!(fluid(&world, 11, 1, 8), (water, 1));
+        assert_eq!(fluid(&world, 12, 1, 8), (Block::AIR, 0));
+        assert_eq!(fluid(&world, 4, 2, 8), (Block::AIR, 0));
+        assert_eq!(fluid(&world, 4, 1, 9), (water, SOURCE_LEVEL - 1));
+        // the water that reaches the hole falls through it and spreads out again below the floor
+        assert_eq!(fluid(&world, 4, 0, 4), (water, FALLING_LEVEL));
+        assert_eq!(fluid(&world, 4, -16, 4), (water, FALLING_LEVEL));
+        assert_eq!(fluid(&world, 5, -16, 4), (water, FALLING_LEVEL - 1));
+
+        // without the source all the flowing water dries up
+        world.set_block(cgmath::vec3(4, 1, 8), stone);
+        world.run_ticks(200);
+        assert_eq!(fluid(&world, 5, 1, 8), (Block::AIR, 0));
+        assert_eq!(fluid(&world, 4, -16, 4), (Block::AIR, 0));
+    }
+
+    #[test]
+    fn lava_hardens_where_it_meets_water() {
+        let mut world = World::test_floor("stone");
+        let registry = world.registry();
+        let (water, lava, stone) = (
+            registry.by_name("water").unwrap(),
+            registry.by_name("lava").unwrap(),
+            registry.by_name("stone").unwrap(),
+        );
+        // a channel along the x axis so the fluids can only flow towards each other
+        for x in 0..16 {
+            world.set_block(cgmath::vec3(x, 1, 1), stone);
+            world.set_block(cgmath::vec3(x, 1, 3), stone);
+        }
+        world.set_block(cgmath::vec3(2, 1, 2), water);
+        world.set_block(cgmath::vec3(12, 1, 2), lava);
+        world.run_ticks(200);
+
+        let blocks = (2..=12)
+            .map(|x| world.get_block(cgmath::vec3(x, 1, 2)).unwrap())
+            .collect::<Vec<_>>();
+        let hardened = blocks.iter().position(|&block| block == stone).unwrap();
+        assert!(blocks[..hardened].iter().all(|&block| block == water));
+        assert!(blocks[hardened + 1..].iter().all(|&block| block == lava));
+    }
+}
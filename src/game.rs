@@ -1,15 +1,33 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
 
 use crate::{
-    chunk::{Block, Chunk},
-    math::Motor,
+    biome::Biome,
+    chunk::{self, Block, Chunk, MeshingMode, CHUNK_SIZE},
+    light::{Light, MAX_LIGHT},
+    math::{Motor, Point},
+    model,
+    player::{self, Aabb, Player},
+    raycast::{raycast, Ray, RaycastHit},
+    registry::BlockRegistry,
+    state::{BlockState, Direction},
+    streaming::LoadRadius,
     texture::Texture,
+    tick::TICKS_PER_SECOND,
+    worker::WorkerPool,
+    world::{self, SourcedChunk, World},
 };
-use anyhow::bail;
-use cgmath::InnerSpace;
-use encase::{DynamicStorageBuffer, ShaderSize, ShaderType, UniformBuffer};
+use anyhow::{bail, Context};
+use cgmath::{ElementWise, InnerSpace};
+use encase::{ShaderSize, ShaderType, StorageBuffer, UniformBuffer};
 use wgpu::util::DeviceExt as _;
-use winit::{keyboard::KeyCode, window::Window};
+use winit::{
+    event::MouseButton,
+    keyboard::KeyCode,
+    window::{CursorGrabMode, Window},
+};
 
 #[derive(ShaderType)]
 struct Camera {
@@ -19,34 +37,421 @@ struct Camera {
     far_clip: f32,
 }
 
-#[derive(ShaderType)]
+#[derive(Clone, Copy, ShaderType)]
 struct Face {
     position: cgmath::Vector3<f32>,
+    size: cgmath::Vector3<f32>,
     normal: cgmath::Vector3<f32>,
-    color: cgmath::Vector3<f32>,
+    color: cgmath::Vector4<f32>,
+    /// The sky light and block light in front of the face, in the range `0.0..=1.0`
+    light: cgmath::Vector2<f32>,
+    /// The ambient occlusion of each corner of the face, from 0 for fully occluded to 1
+    ambient_occlusion: cgmath::Vector4<f32>,
+    /// Which way the face is facing, see [`face_normal`]
+    direction: u32,
 }
 
 #[derive(ShaderType)]
 struct Faces<'a> {
-    vertices: [cgmath::Vector3<f32>; 6],
+    /// The corners of a unit quad centered on a block for each face direction,
+    /// indexed like [`chunk::Quad::ambient_occlusion`]
+    corners: [[cgmath::Vector3<f32>; 4]; FACE_DIRECTIONS],
+    /// The corners of the two triangles of a quad for each face direction, the quad is split along
+    /// the first diagonal in the first set of triangles and along the other diagonal in the second
+    triangles: [[[u32; 6]; 2]; FACE_DIRECTIONS],
     #[size(runtime)]
     faces: &'a [Face],
 }
 
-struct FaceInfo {
-    start_offset: u32,
+/// The normals of each face direction, in the order back, front, top, bottom, left, right
+const FACE_NORMALS: [cgmath::Vector3<i32>; 6] = [
+    cgmath::Vector3::new(-1, 0, 0),
+    cgmath::Vector3::new(1, 0, 0),
+    cgmath::Vector3::new(0, 1, 0),
+    cgmath::Vector3::new(0, -1, 0),
+    cgmath::Vector3::new(0, 0, -1),
+    cgmath::Vector3::new(0, 0, 1),
+];
+
+/// The six sides of a cube, followed by the front and back of the two diagonal planes of a cross model
+const FACE_DIRECTIONS: usize = 10;
+
+/// The first of the face directions of cross models
+const CROSS_DIRECTION: usize = 6;
+
+fn face_normal(direction: usize) -> cgmath::Vector3<f32> {
+    if direction < CROSS_DIRECTION {
+        return FACE_NORMALS[direction].cast().unwrap();
+    }
+    [
+        cgmath::vec3(1.0, 0.0, -1.0),
+        cgmath::vec3(-1.0, 0.0, 1.0),
+        cgmath::vec3(1.0, 0.0, 1.0),
+        cgmath::vec3(-1.0, 0.0, -1.0),
+    ][direction - CROSS_DIRECTION]
+        .normalize()
+}
+
+fn face_corners(direction: usize) -> [cgmath::Vector3<f32>; 4] {
+    if direction >= CROSS_DIRECTION {
+        // the plane goes through the middle of the block from one vertical edge to the opposite one
+        let normal = face_normal(direction) * std::f32::consts::SQRT_2;
+        let diagonal = cgmath::vec3(-normal.z, 0.0, normal.x);
+        return [0, 1, 2, 3].map(|corner| {
+            diagonal * ((corner & 1) as f32 - 0.5)
+                + cgmath::vec3(0.0, (corner >> 1) as f32 - 0.5, 0.0)
+        });
+    }
+    let normal = FACE_NORMALS[direction];
+    let normal_axis = (0..3).find(|&axis| normal[axis] != 0).unwrap();
+    let (u_axis, v_axis) = chunk::plane_axes(normal_axis);
+    [0, 1, 2, 3].map(|corner| {
+        let mut position = normal.cast::<f32>().unwrap() * 0.5;
+        position[u_axis] = (corner & 1) as f32 - 0.5;
+        position[v_axis] = (corner >> 1) as f32 - 0.5;
+        position
+    })
+}
+
+fn face_triangles(direction: usize) -> [[u32; 6]; 2] {
+    let corners = face_corners(direction);
+    let normal = face_normal(direction);
+    // triangles have to be wound clockwise when looking at the front of the face
+    let reversed = (corners[1] - corners[0])
+        .cross(corners[3] - corners[0])
+        .dot(normal)
+        > 0.0;
+    [[0, 1, 3, 0, 3, 2], [0, 1, 2, 1, 3, 2]].map(|[a, b, c, d, e, f]| {
+        if reversed {
+            [a, c, b, d, f, e]
+        } else {
+            [a, b, c, d, e, f]
+        }
+    })
+}
+
+/// Serializes faces into the layout of the `Faces` storage buffer in the shader
+fn faces_buffer_contents(faces: &[Face]) -> anyhow::Result<Vec<u8>> {
+    let mut buffer = StorageBuffer::new(vec![]);
+    buffer.write(&Faces {
+        corners: std::array::from_fn(face_corners),
+        triangles: std::array::from_fn(face_triangles),
+        faces,
+    })?;
+    let mut buffer = buffer.into_inner();
+    // a binding must be big enough for at least one face, even if there are none
+    buffer.resize(buffer.len().max(Faces::min_size().get() as usize), 0);
+    Ok(buffer)
+}
+
+fn create_faces_bind_group(
+    device: &wgpu::Device,
+    faces_bind_group_layout: &wgpu::BindGroupLayout,
+    faces_storage_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Faces Bind Group"),
+        layout: faces_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: faces_storage_buffer.as_entire_binding(),
+        }],
+    })
+}
+
+/// Faces that are uploaded again whenever they change, the storage buffer is reused
+/// and only grows when they don't fit
+struct DynamicFaces {
+    label: &'static str,
+    storage_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
     count: u32,
 }
 
+impl DynamicFaces {
+    fn new(
+        device: &wgpu::Device,
+        faces_bind_group_layout: &wgpu::BindGroupLayout,
+        label: &'static str,
+        faces: &[Face],
+    ) -> anyhow::Result<Self> {
+        let storage_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            contents: &faces_buffer_contents(faces)?,
+        });
+        Ok(Self {
+            label,
+            bind_group: create_faces_bind_group(device, faces_bind_group_layout, &storage_buffer),
+            storage_buffer,
+            count: faces.len().try_into()?,
+        })
+    }
+
+    fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces_bind_group_layout: &wgpu::BindGroupLayout,
+        faces: &[Face],
+    ) -> anyhow::Result<()> {
+        let contents = faces_buffer_contents(faces)?;
+        if contents.len() as u64 > self.storage_buffer.size() {
+            self.storage_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(self.label),
+                size: (contents.len() as u64).next_power_of_two(),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            self.bind_group =
+                create_faces_bind_group(device, faces_bind_group_layout, &self.storage_buffer);
+        }
+        queue.write_buffer(&self.storage_buffer, 0, &contents);
+        self.count = faces.len().try_into()?;
+        Ok(())
+    }
+}
+
+struct ChunkMesh {
+    /// Remeshing the chunk writes the new faces into the same buffer when they fit
+    opaque_faces: DynamicFaces,
+    /// Translucent faces are drawn after all the opaque faces, chunk by chunk from back to front
+    translucent_faces: Vec<Face>,
+    /// The translucent faces sorted back to front from where the camera was when they were last sorted,
+    /// this is only created once the chunk has translucent faces
+    sorted_translucent_faces: Option<DynamicFaces>,
+    /// Whether `translucent_faces` changed since they were last sorted
+    translucent_faces_changed: bool,
+}
+
+/// The lines along the edges of the boxes of the block that the camera is aimed at
+struct Outline {
+    vertex_buffer: wgpu::Buffer,
+    vertex_count: u32,
+}
+
+impl Outline {
+    const LABEL: &'static str = "Outline Vertex Buffer";
+
+    fn new(device: &wgpu::Device) -> Self {
+        Self {
+            vertex_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(Self::LABEL),
+                // enough for a block made of two boxes, like stairs
+                size: (2 * 24 * std::mem::size_of::<[f32; 3]>()) as u64,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            vertex_count: 0,
+        }
+    }
+
+    /// Outlines the boxes, from `min` to `max` in block space. The vertex buffer grows when they don't fit
+    fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        boxes: &[(cgmath::Vector3<f32>, cgmath::Vector3<f32>)],
+    ) -> anyhow::Result<()> {
+        let mut contents = vec![];
+        for &(min, max) in boxes {
+            let corner = |index: usize| {
+                cgmath::vec3(
+                    if index & 1 == 0 { min.x } else { max.x },
+                    if index & 2 == 0 { min.y } else { max.y },
+                    if index & 4 == 0 { min.z } else { max.z },
+                )
+            };
+            // every edge goes from a corner to the corner that differs from it along one axis
+            for index in 0..8 {
+                for axis in [1, 2, 4] {
+                    if index & axis == 0 {
+                        for vertex in [corner(index), corner(index | axis)] {
+                            contents.extend([vertex.x, vertex.y, vertex.z].map(f32::to_le_bytes));
+                        }
+                    }
+                }
+            }
+        }
+        let contents = contents.concat();
+        if contents.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(Self::LABEL),
+                size: (contents.len() as u64).next_power_of_two(),
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        }
+        if !contents.is_empty() {
+            queue.write_buffer(&self.vertex_buffer, 0, &contents);
+        }
+        self.vertex_count = (contents.len() / std::mem::size_of::<[f32; 3]>()).try_into()?;
+        Ok(())
+    }
+}
+
+/// Work on a chunk that is done on the worker threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Job {
+    /// Reading the chunk from the save or generating it
+    Load(cgmath::Vector3<i32>),
+    /// Generating the faces of the chunk, only the mesh with the latest version of each chunk is used
+    Mesh(cgmath::Vector3<i32>, u64),
+}
+
+impl Job {
+    fn chunk_position(self) -> cgmath::Vector3<i32> {
+        match self {
+            Job::Load(chunk_position) | Job::Mesh(chunk_position, _) => chunk_position,
+        }
+    }
+}
+
+enum JobResult {
+    /// Boxed because a chunk is much bigger than the other results
+    Load(anyhow::Result<Box<SourcedChunk>>),
+    Mesh {
+        opaque_faces: Vec<Face>,
+        translucent_faces: Vec<Face>,
+    },
+}
+
+/// How the mouse turns the camera while the cursor is grabbed, read from [`SETTINGS_PATH`]
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(default)]
+struct MouseLook {
+    /// In radians per unit of mouse movement, which is roughly a pixel
+    sensitivity: f32,
+    /// Moving the mouse up looks down instead of up
+    invert_y: bool,
+}
+
+impl Default for MouseLook {
+    fn default() -> Self {
+        Self {
+            sensitivity: 0.0025,
+            invert_y: false,
+        }
+    }
+}
+
+impl MouseLook {
+    /// Reads the settings from a RON file, the defaults are used if the file doesn't exist
+    fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::default())
+            }
+            Err(error) => return Err(error).with_context(|| format!("reading {}", path.display())),
+        };
+        ron::from_str(&source).with_context(|| format!("loading {}", path.display()))
+    }
+}
+
+/// Jobs for the chunks closest to the center run first
+fn job_priority(center: cgmath::Vector3<i32>, chunk_position: cgmath::Vector3<i32>) -> u64 {
+    let offset = chunk_position - center;
+    (offset.x * offset.x + offset.y * offset.y + offset.z * offset.z) as u64
+}
+
+const BLOCKS_PATH: &str = "assets/blocks.ron";
+const SETTINGS_PATH: &str = "settings.ron";
+const SAVE_DIRECTORY: &str = "saves/world";
+const MESHING_MODE: MeshingMode = MeshingMode::Greedy;
+const LOAD_RADIUS: LoadRadius = LoadRadius {
+    horizontal: 4,
+    vertical: 2,
+};
+const TICK_DURATION: std::time::Duration =
+    std::time::Duration::from_nanos(1_000_000_000 / TICKS_PER_SECOND as u64);
+/// If a frame takes longer than this many ticks the world slows down instead of trying to catch up
+const MAX_TICKS_PER_UPDATE: u32 = 5;
+/// How far the camera moves before the translucent faces are sorted again, in blocks
+const TRANSLUCENT_SORT_DISTANCE: f32 = 1.0;
+/// How far up or down the camera can look, so it doesn't flip over
+const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+/// How far away blocks can be broken and placed, in blocks
+const REACH: f32 = 5.0;
+const EDIT_COOLDOWN: std::time::Duration = std::time::Duration::from_millis(250);
+/// The names of the blocks that can be placed, in the order of the number keys that select them
+const HOTBAR: [&str; 9] = [
+    "stone",
+    "dirt",
+    "log",
+    "trapdoor",
+    "glass",
+    "lamp",
+    "stone_slab",
+    "cobblestone_stairs",
+    "furnace",
+];
+const HOTBAR_KEYS: [KeyCode; HOTBAR.len()] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
 pub struct Game {
-    face_infos: Vec<FaceInfo>,
-    vertices_faces_bind_group: wgpu::BindGroup,
+    world: World,
+    /// Every loaded chunk has a mesh, dropping a mesh frees its GPU buffers
+    chunk_meshes: HashMap<cgmath::Vector3<i32>, ChunkMesh>,
+    load_radius: LoadRadius,
+    /// The chunk that the chunks were last loaded around
+    load_center: Option<cgmath::Vector3<i32>>,
+    workers: WorkerPool<Job, JobResult>,
+    /// The chunks that are being read or generated on the workers
+    loading_chunks: HashSet<cgmath::Vector3<i32>>,
+    /// Writes the chunks that were unloaded into the save on a single thread, so two saves never write
+    /// the same region file at once
+    saver: WorkerPool<Vec<cgmath::Vector3<i32>>, anyhow::Result<()>>,
+    /// The chunks that are being saved, they are only loaded again once their save has finished
+    saving_chunks: HashSet<cgmath::Vector3<i32>>,
+    /// The chunks that couldn't be read, they are left out instead of being tried again or generated
+    /// so the broken chunk in the save isn't overwritten
+    failed_chunks: HashSet<cgmath::Vector3<i32>>,
+    /// The version of the latest mesh job of each chunk whose mesh is being generated
+    mesh_versions: HashMap<cgmath::Vector3<i32>, u64>,
+    next_mesh_version: u64,
+    /// The chunks whose meshes are out of date, they are remeshed once at the end of each update
+    /// no matter how many of their blocks changed
+    dirty_chunks: HashSet<cgmath::Vector3<i32>>,
+    /// The time that has passed since the last world tick
+    tick_time: std::time::Duration,
 
     camera: Camera,
     camera_uniform_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
 
+    faces_bind_group_layout: wgpu::BindGroupLayout,
+    /// The chunks that have translucent faces, back to front from where the camera was when they were last
+    /// sorted, along with the squared distance to their centers
+    translucent_chunk_order: Vec<(f32, cgmath::Vector3<i32>)>,
+    /// Where the camera was when the translucent faces were last sorted
+    translucent_sort_position: Option<cgmath::Vector3<f32>>,
+    falling_block_faces: DynamicFaces,
+    /// Sorted back to front every frame, and drawn in between the chunks by their distance
+    falling_block_translucent_faces: DynamicFaces,
+    /// The squared distances of the translucent falling block faces to the camera, in the same order
+    falling_block_translucent_distances: Vec<f32>,
+
     render_pipeline: wgpu::RenderPipeline,
+    translucent_render_pipeline: wgpu::RenderPipeline,
+    outline_render_pipeline: wgpu::RenderPipeline,
+    outline: Outline,
+    /// The block that the camera is aimed at, if it is close enough to be edited
+    target: Option<RaycastHit>,
+
+    player: Player,
+    /// Whether the camera flies freely through blocks instead of following the player
+    noclip: bool,
     depth_buffer: Texture,
 
     surface: wgpu::Surface,
@@ -55,6 +460,17 @@ pub struct Game {
     config: wgpu::SurfaceConfiguration,
 
     pub(crate) pressed_keys: HashSet<winit::keyboard::KeyCode>,
+    pub(crate) pressed_buttons: HashSet<MouseButton>,
+    mouse_look: MouseLook,
+    /// The mouse movement since the last update, while the cursor is grabbed
+    mouse_motion: cgmath::Vector2<f32>,
+    /// The cursor is hidden and kept inside of the window while playing
+    cursor_grabbed: bool,
+    /// The time until holding a mouse button down breaks or places another block
+    edit_cooldown: std::time::Duration,
+    /// The blocks that can be placed, selected with the number keys
+    hotbar: [Block; HOTBAR.len()],
+    selected_slot: usize,
 
     // the window must be dropped last because its referenced by the surface
     window: Window,
@@ -159,223 +575,183 @@ impl Game {
             }],
         });
 
-        let mut chunk = Chunk {
-            blocks: Box::new(std::array::from_fn(|_| {
-                std::array::from_fn(|_| std::array::from_fn(|_| Block::Air))
-            })),
-        };
-        chunk.blocks[0][0][0] = Block::Stone;
-        chunk.blocks[0][0][1] = Block::Stone;
-        chunk.blocks[0][2][0] = Block::Stone;
-        chunk.blocks[1][2][0] = Block::Stone;
-        chunk.blocks[1][2][1] = Block::Stone;
-        chunk.blocks[1][3][1] = Block::Stone;
-
-        let mut face_infos = vec![];
-        let faces_storage_buffer = {
-            let mut buffer = Vec::with_capacity(Faces::min_size().get() as _);
-            let faces = chunk.generate_faces();
-
-            macro_rules! face {
-                ($face:ident, $normal:expr, $vertices:expr $(,)?) => {{
-                    let start_offset = buffer.len().try_into()?;
-
-                    let mut storage_buffer = DynamicStorageBuffer::new(buffer);
-                    storage_buffer.set_offset(start_offset as _);
-                    let faces = faces
-                        .$face
-                        .into_iter()
-                        .map(|(position, block)| Face {
-                            position: position.cast().unwrap(),
-                            normal: $normal,
-                            color: match block {
-                                Block::Air => unreachable!(),
-                                Block::Stone => cgmath::vec3(0.2, 0.2, 0.2),
-                            },
-                        })
-                        .collect::<Vec<_>>();
-                    let face_data = Faces {
-                        vertices: $vertices,
-                        faces: &faces,
-                    };
-                    storage_buffer.write(&face_data)?;
-                    face_infos.push(FaceInfo {
-                        start_offset,
-                        count: face_data.faces.len().try_into()?,
-                    });
-
-                    buffer = storage_buffer.into_inner();
-                    buffer.resize(((buffer.len() + (256 - 1)) & !(256 - 1)), 0);
-                }};
-            }
-
-            face!(
-                back,
-                cgmath::vec3(-1.0, 0.0, 0.0),
-                [
-                    cgmath::vec3(-0.5, -0.5, -0.5),
-                    cgmath::vec3(-0.5, 0.5, -0.5),
-                    cgmath::vec3(-0.5, -0.5, 0.5),
-                    cgmath::vec3(-0.5, -0.5, 0.5),
-                    cgmath::vec3(-0.5, 0.5, -0.5),
-                    cgmath::vec3(-0.5, 0.5, 0.5),
-                ],
-            );
-            face!(
-                front,
-                cgmath::vec3(1.0, 0.0, 0.0),
-                [
-                    cgmath::vec3(0.5, 0.5, -0.5),
-                    cgmath::vec3(0.5, -0.5, -0.5),
-                    cgmath::vec3(0.5, -0.5, 0.5),
-                    cgmath::vec3(0.5, 0.5, -0.5),
-                    cgmath::vec3(0.5, -0.5, 0.5),
-                    cgmath::vec3(0.5, 0.5, 0.5),
-                ],
-            );
-
-            face!(
-                top,
-                cgmath::vec3(0.0, 1.0, 0.0),
-                [
-                    cgmath::vec3(-0.5, 0.5, 0.5),
-                    cgmath::vec3(-0.5, 0.5, -0.5),
-                    cgmath::vec3(0.5, 0.5, -0.5),
-                    cgmath::vec3(-0.5, 0.5, 0.5),
-                    cgmath::vec3(0.5, 0.5, -0.5),
-                    cgmath::vec3(0.5, 0.5, 0.5),
-                ],
-            );
-            face!(
-                bottom,
-                cgmath::vec3(0.0, -1.0, 0.0),
-                [
-                    cgmath::vec3(-0.5, -0.5, -0.5),
-                    cgmath::vec3(-0.5, -0.5, 0.5),
-                    cgmath::vec3(0.5, -0.5, -0.5),
-                    cgmath::vec3(0.5, -0.5, -0.5),
-                    cgmath::vec3(-0.5, -0.5, 0.5),
-                    cgmath::vec3(0.5, -0.5, 0.5),
-                ],
-            );
-
-            face!(
-                left,
-                cgmath::vec3(0.0, 0.0, -1.0),
-                [
-                    cgmath::vec3(-0.5, 0.5, -0.5),
-                    cgmath::vec3(-0.5, -0.5, -0.5),
-                    cgmath::vec3(0.5, -0.5, -0.5),
-                    cgmath::vec3(-0.5, 0.5, -0.5),
-                    cgmath::vec3(0.5, -0.5, -0.5),
-                    cgmath::vec3(0.5, 0.5, -0.5),
-                ],
-            );
-            face!(
-                right,
-                cgmath::vec3(0.0, 0.0, 1.0),
-                [
-                    cgmath::vec3(-0.5, -0.5, 0.5),
-                    cgmath::vec3(-0.5, 0.5, 0.5),
-                    cgmath::vec3(0.5, -0.5, 0.5),
-                    cgmath::vec3(0.5, -0.5, 0.5),
-                    cgmath::vec3(-0.5, 0.5, 0.5),
-                    cgmath::vec3(0.5, 0.5, 0.5),
-                ],
-            );
-
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Faces Storage Buffer"),
-                usage: wgpu::BufferUsages::STORAGE,
-                contents: &buffer,
-            })
-        };
-
-        let vertices_faces_bind_group_layout =
+        let faces_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("Vertices Faces Bind Group Layout"),
+                label: Some("Faces Bind Group Layout"),
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
                     visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Storage { read_only: true },
-                        has_dynamic_offset: true,
+                        has_dynamic_offset: false,
                         min_binding_size: Some(Faces::min_size()),
                     },
                     count: None,
                 }],
             });
 
-        let vertices_faces_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Vertices Faces Bind Group"),
-            layout: &vertices_faces_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                    buffer: &faces_storage_buffer,
-                    offset: 0,
-                    size: wgpu::BufferSize::new(
-                        faces_storage_buffer.size() / face_infos.len() as wgpu::BufferAddress,
-                    ),
-                }),
-            }],
-        });
+        let registry = BlockRegistry::load(BLOCKS_PATH)?;
+        let world = if World::save_exists(SAVE_DIRECTORY) {
+            World::load(SAVE_DIRECTORY, registry)?
+        } else {
+            World::new(0, registry)?
+        };
+        // the top of the highest block is half a block above its position
+        let player = Player::new(cgmath::vec3(
+            -2.0,
+            world.generator().height(-2, 0) as f32 + 1.0,
+            0.0,
+        ));
+        let mut hotbar = [Block::AIR; HOTBAR.len()];
+        for (slot, name) in hotbar.iter_mut().zip(HOTBAR) {
+            *slot = world.registry().by_name(name)?;
+        }
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout, &vertices_faces_bind_group_layout],
+                bind_group_layouts: &[&camera_bind_group_layout, &faces_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vertex",
-                buffers: &[],
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Cw,
-                cull_mode: Some(wgpu::Face::Back),
-                // cull_mode: None,
-                unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth32Float,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "pixel",
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: None,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-            }),
-            multiview: None,
-        });
+        let create_render_pipeline = |label, blend, depth_write_enabled| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vertex",
+                    buffers: &[],
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Cw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    // cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "pixel",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            })
+        };
+        let render_pipeline = create_render_pipeline("Render Pipeline", None, true);
+        // translucent faces are depth tested against the opaque faces, but don't hide each other
+        let translucent_render_pipeline = create_render_pipeline(
+            "Translucent Render Pipeline",
+            Some(wgpu::BlendState::ALPHA_BLENDING),
+            false,
+        );
+
+        let outline_render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Outline Render Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        // the outline is depth tested against the opaque faces so it is hidden behind other blocks,
+        // the shader moves it a bit towards the camera so it isn't hidden by the faces it lies on
+        let outline_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Outline Render Pipeline"),
+                layout: Some(&outline_render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "outline_vertex",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<[f32; 3]>() as u64,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x3],
+                    }],
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Cw,
+                    cull_mode: None,
+                    unclipped_depth: false,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "outline_pixel",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            });
+
+        let falling_block_faces = DynamicFaces::new(
+            &device,
+            &faces_bind_group_layout,
+            "Falling Block Faces Storage Buffer",
+            &[],
+        )?;
+        let falling_block_translucent_faces = DynamicFaces::new(
+            &device,
+            &faces_bind_group_layout,
+            "Falling Block Translucent Faces Storage Buffer",
+            &[],
+        )?;
 
-        Ok(Game {
-            face_infos,
-            vertices_faces_bind_group,
+        let mut game = Game {
+            world,
+            chunk_meshes: HashMap::new(),
+            load_radius: LOAD_RADIUS,
+            load_center: None,
+            // one thread is left for rendering
+            workers: WorkerPool::new(
+                std::thread::available_parallelism().map_or(1, |count| count.get() - 1),
+            ),
+            loading_chunks: HashSet::new(),
+            saver: WorkerPool::new(1),
+            saving_chunks: HashSet::new(),
+            failed_chunks: HashSet::new(),
+            mesh_versions: HashMap::new(),
+            next_mesh_version: 0,
+            dirty_chunks: HashSet::new(),
+            tick_time: std::time::Duration::ZERO,
 
             camera: Camera {
-                transform: Motor::translation(cgmath::vec3(-2.0, 0.0, 0.0)),
+                transform: Motor::translation(player.eye_position()),
                 aspect: size.width as f32 / size.height as f32,
                 near_clip: 0.01,
                 far_clip: 100.0,
@@ -383,7 +759,21 @@ impl Game {
             camera_uniform_buffer,
             camera_bind_group,
 
+            faces_bind_group_layout,
+            translucent_chunk_order: vec![],
+            translucent_sort_position: None,
+            falling_block_faces,
+            falling_block_translucent_faces,
+            falling_block_translucent_distances: vec![],
+
             render_pipeline,
+            translucent_render_pipeline,
+            outline_render_pipeline,
+            outline: Outline::new(&device),
+            target: None,
+
+            player,
+            noclip: false,
             depth_buffer,
 
             surface,
@@ -392,9 +782,21 @@ impl Game {
             config,
 
             pressed_keys: HashSet::new(),
+            pressed_buttons: HashSet::new(),
+            mouse_look: MouseLook::load(SETTINGS_PATH).unwrap_or_else(|error| {
+                eprintln!("{error:?}, using the default mouse settings");
+                MouseLook::default()
+            }),
+            mouse_motion: cgmath::vec2(0.0, 0.0),
+            cursor_grabbed: false,
+            edit_cooldown: std::time::Duration::ZERO,
+            hotbar,
+            selected_slot: 0,
 
             window,
-        })
+        };
+        game.stream_chunks();
+        Ok(game)
     }
 
     pub fn update(&mut self, dt: std::time::Duration) -> anyhow::Result<()> {
@@ -445,24 +847,469 @@ impl Game {
             roll += 1.0;
         }
 
-        if movement.magnitude2() > 0.001 {
+        if !self.noclip {
+            self.walk(movement, ts);
+        } else if movement.magnitude2() > 0.001 {
             self.camera.transform = self
                 .camera
                 .transform
                 .pre_apply(Motor::translation(movement.normalize() * CAMERA_SPEED * ts));
         }
 
-        self.camera.transform = self.camera.transform.pre_apply(
-            Motor::rotation_xy(pitch * CAMERA_ROTATION_SPEED * ts)
-                .apply(Motor::rotation_xz(yaw * CAMERA_ROTATION_SPEED * ts))
-                .apply(Motor::rotation_yz(roll * CAMERA_ROTATION_SPEED * ts)),
+        let mouse_motion = std::mem::replace(&mut self.mouse_motion, cgmath::vec2(0.0, 0.0));
+        let mouse_pitch = if self.mouse_look.invert_y {
+            mouse_motion.y
+        } else {
+            -mouse_motion.y
+        };
+        self.look(
+            yaw * CAMERA_ROTATION_SPEED * ts + mouse_motion.x * self.mouse_look.sensitivity,
+            pitch * CAMERA_ROTATION_SPEED * ts + mouse_pitch * self.mouse_look.sensitivity,
+        );
+        self.camera.transform = self
+            .camera
+            .transform
+            .pre_apply(Motor::rotation_yz(roll * CAMERA_ROTATION_SPEED * ts));
+        for (slot, key) in HOTBAR_KEYS.into_iter().enumerate() {
+            if self.pressed_keys.contains(&key) {
+                self.selected_slot = slot;
+            }
+        }
+        self.stream_chunks();
+        self.receive_finished_saves();
+        self.receive_finished_jobs()?;
+        self.edit_blocks(dt)?;
+        self.target = raycast(
+            &self.world,
+            Ray::from_transform(self.camera.transform),
+            REACH,
+        );
+
+        self.tick_time += dt;
+        let mut changed = vec![];
+        for _ in 0..MAX_TICKS_PER_UPDATE {
+            if self.tick_time < TICK_DURATION {
+                break;
+            }
+            self.tick_time -= TICK_DURATION;
+            changed.extend(self.world.tick());
+        }
+        self.tick_time = self.tick_time.min(TICK_DURATION);
+        self.dirty_chunks.extend(chunks_showing_blocks(&changed));
+
+        let dirty_chunks = std::mem::take(&mut self.dirty_chunks);
+        self.remesh_chunks(dirty_chunks);
+        Ok(())
+    }
+
+    /// Moves the player along the ground towards where the camera looks, and the camera along with the player.
+    /// `movement` is the same as for flying, where `x` is forward, `z` is to the right and `y` is up
+    fn walk(&mut self, movement: cgmath::Vector3<f32>, ts: f32) {
+        let forward = self.camera.transform.forward();
+        let forward = cgmath::vec2(forward.x, forward.z);
+        let mut walk = cgmath::vec2(0.0, 0.0);
+        // looking straight up or down doesn't point anywhere along the ground
+        if forward.magnitude2() > 0.001 {
+            let forward = forward.normalize();
+            let right = cgmath::vec2(-forward.y, forward.x);
+            let direction = forward * movement.x + right * movement.z;
+            if direction.magnitude2() > 0.001 {
+                walk = direction.normalize() * player::WALK_SPEED;
+            }
+        }
+        self.player.step(&self.world, walk, movement.y > 0.0, ts);
+
+        let camera_position: cgmath::Vector3<f32> =
+            Point::IDENTITY.transform(self.camera.transform).into();
+        self.camera.transform = self.camera.transform.apply(Motor::translation(
+            self.player.eye_position() - camera_position,
+        ));
+    }
+
+    /// Turns the camera around the vertical axis by `yaw` and then up or down by `pitch`, in radians.
+    /// Turning up or down stops at [`MAX_PITCH`]
+    fn look(&mut self, yaw: f32, pitch: f32) {
+        let current_pitch = self.camera.transform.forward().y.clamp(-1.0, 1.0).asin();
+        let pitch = (current_pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH) - current_pitch;
+        let camera_position: cgmath::Vector3<f32> =
+            Point::IDENTITY.transform(self.camera.transform).into();
+        // yaw turns around the vertical axis of the world instead of the camera's, so looking up or down
+        // doesn't tilt the camera to the side
+        self.camera.transform = self
+            .camera
+            .transform
+            .pre_apply(Motor::rotation_xy(pitch))
+            .apply(Motor::translation(-camera_position))
+            .apply(Motor::rotation_xz(yaw))
+            .apply(Motor::translation(camera_position));
+    }
+
+    /// Handles the keys that do something once when they are pressed, instead of for as long as they are held
+    pub fn key_pressed(&mut self, key: KeyCode) {
+        match key {
+            KeyCode::KeyF => {
+                self.noclip = !self.noclip;
+                if !self.noclip {
+                    // the player lands wherever the camera flew to
+                    let camera_position: cgmath::Vector3<f32> =
+                        Point::IDENTITY.transform(self.camera.transform).into();
+                    self.player =
+                        Player::new(camera_position - cgmath::vec3(0.0, player::EYE_HEIGHT, 0.0));
+                }
+            }
+            KeyCode::Escape => self.release_cursor(),
+            _ => {}
+        }
+    }
+
+    /// Clicking while the cursor isn't grabbed only grabs it, it doesn't break or place a block
+    pub fn mouse_pressed(&mut self, button: MouseButton) {
+        if self.cursor_grabbed {
+            self.pressed_buttons.insert(button);
+        } else {
+            self.grab_cursor();
+        }
+    }
+
+    /// `delta` is in the units of the mouse, which are roughly pixels but don't depend on the cursor
+    pub fn mouse_moved(&mut self, delta: (f64, f64)) {
+        if self.cursor_grabbed {
+            self.mouse_motion += cgmath::vec2(delta.0 as f32, delta.1 as f32);
+        }
+    }
+
+    /// Hides the cursor and keeps it inside of the window, so the mouse can turn the camera
+    pub fn grab_cursor(&mut self) {
+        // platforms support different ways of keeping the cursor in the window
+        let grabbed = self
+            .window
+            .set_cursor_grab(CursorGrabMode::Locked)
+            .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined));
+        if let Err(error) = grabbed {
+            eprintln!("failed to grab the cursor: {error}");
+            return;
+        }
+        self.window.set_cursor_visible(false);
+        self.cursor_grabbed = true;
+    }
+
+    fn release_cursor(&mut self) {
+        if let Err(error) = self.window.set_cursor_grab(CursorGrabMode::None) {
+            eprintln!("failed to release the cursor: {error}");
+        }
+        self.window.set_cursor_visible(true);
+        self.cursor_grabbed = false;
+        self.mouse_motion = cgmath::vec2(0.0, 0.0);
+        self.pressed_buttons.clear();
+    }
+
+    /// Starts loading the chunks within the load radius around the camera on the workers, and drops the chunks
+    /// outside of it and starts saving them. This only does anything when the camera moves into a different chunk
+    fn stream_chunks(&mut self) {
+        let camera_position: cgmath::Vector3<f32> =
+            Point::IDENTITY.transform(self.camera.transform).into();
+        let (center, _) = world::split_block_position(camera_position.map(|x| x.floor() as i32));
+        if self.load_center == Some(center) {
+            return;
+        }
+        self.load_center = Some(center);
+
+        let unloaded = self
+            .world
+            .chunks()
+            .map(|(chunk_position, _)| chunk_position)
+            .filter(|&chunk_position| !self.load_radius.contains(center, chunk_position))
+            .collect::<Vec<_>>();
+        let chunks = self.world.unload_chunks(&unloaded);
+        if !chunks.is_empty() {
+            self.saving_chunks.extend(&unloaded);
+            self.saver.submit(unloaded.clone(), 0, move || {
+                world::save_chunks(
+                    Path::new(SAVE_DIRECTORY),
+                    chunks
+                        .iter()
+                        .map(|(chunk_position, chunk)| (*chunk_position, chunk)),
+                )
+            });
+        }
+
+        // jobs that already started still finish, their results are ignored when they come back
+        let load_radius = self.load_radius;
+        self.workers
+            .cancel(|job| !load_radius.contains(center, job.chunk_position()));
+        self.workers
+            .reprioritize(|job| job_priority(center, job.chunk_position()));
+        self.loading_chunks
+            .retain(|&chunk_position| load_radius.contains(center, chunk_position));
+        self.mesh_versions
+            .retain(|&chunk_position, _| load_radius.contains(center, chunk_position));
+
+        for chunk_position in load_radius.chunk_positions(center) {
+            self.load_chunk(center, chunk_position);
+        }
+
+        self.dirty_chunks.extend(neighborhoods(unloaded));
+    }
+
+    /// Starts loading the chunk on the workers, unless it is already loaded or being loaded.
+    /// A chunk that is still being saved is loaded when its save has finished, so it doesn't read the old one
+    fn load_chunk(&mut self, center: cgmath::Vector3<i32>, chunk_position: cgmath::Vector3<i32>) {
+        if self.world.chunk(chunk_position).is_some()
+            || self.saving_chunks.contains(&chunk_position)
+            || self.failed_chunks.contains(&chunk_position)
+            || !self.loading_chunks.insert(chunk_position)
+        {
+            return;
+        }
+        let chunk_source = self.world.chunk_source();
+        self.workers.submit(
+            Job::Load(chunk_position),
+            job_priority(center, chunk_position),
+            move || {
+                JobResult::Load(
+                    chunk_source
+                        .read_or_generate(Path::new(SAVE_DIRECTORY), chunk_position)
+                        .map(Box::new),
+                )
+            },
         );
+    }
+
+    /// Loads the chunks whose saves have finished again if they are back in the load radius
+    fn receive_finished_saves(&mut self) {
+        for (saved, result) in self.saver.finished().collect::<Vec<_>>() {
+            if let Err(error) = result {
+                eprintln!("failed to save chunks: {error:?}");
+            }
+            for chunk_position in saved {
+                self.saving_chunks.remove(&chunk_position);
+                if let Some(center) = self.load_center {
+                    if self.load_radius.contains(center, chunk_position) {
+                        self.load_chunk(center, chunk_position);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds the chunks that the workers loaded to the world, and uploads the meshes they generated
+    fn receive_finished_jobs(&mut self) -> anyhow::Result<()> {
+        let mut loaded = vec![];
+        for (job, result) in self.workers.finished().collect::<Vec<_>>() {
+            match (job, result) {
+                (Job::Load(chunk_position), JobResult::Load(chunk)) => {
+                    // the chunk left the load radius while it was loading
+                    if !self.loading_chunks.remove(&chunk_position) {
+                        continue;
+                    }
+                    let chunk = match chunk {
+                        Ok(chunk) => chunk,
+                        Err(error) => {
+                            eprintln!("failed to load chunk {chunk_position:?}: {error:?}");
+                            self.failed_chunks.insert(chunk_position);
+                            continue;
+                        }
+                    };
+                    self.world.add_chunk(chunk_position, *chunk);
+                    loaded.push(chunk_position);
+                }
+                (
+                    Job::Mesh(chunk_position, version),
+                    JobResult::Mesh {
+                        opaque_faces,
+                        translucent_faces,
+                    },
+                ) => {
+                    if self.mesh_versions.get(&chunk_position) != Some(&version) {
+                        continue;
+                    }
+                    self.mesh_versions.remove(&chunk_position);
+                    if self.world.chunk(chunk_position).is_some() {
+                        self.upload_chunk_mesh(chunk_position, &opaque_faces, translucent_faces)?;
+                    }
+                }
+                _ => unreachable!("every job returns its own kind of result"),
+            }
+        }
+        self.dirty_chunks.extend(neighborhoods(loaded));
+        Ok(())
+    }
+
+    /// Breaks the targeted block with the left mouse button and places the selected block against it with the
+    /// right one, holding a button down repeats it after a cooldown
+    fn edit_blocks(&mut self, dt: std::time::Duration) -> anyhow::Result<()> {
+        let breaking = self.pressed_buttons.contains(&MouseButton::Left);
+        let placing = self.pressed_buttons.contains(&MouseButton::Right);
+        if !breaking && !placing {
+            self.edit_cooldown = std::time::Duration::ZERO;
+            return Ok(());
+        }
+        self.edit_cooldown = self.edit_cooldown.saturating_sub(dt);
+        if !self.edit_cooldown.is_zero() {
+            return Ok(());
+        }
+        let ray = Ray::from_transform(self.camera.transform);
+        let Some(hit) = raycast(&self.world, ray, REACH) else {
+            return Ok(());
+        };
+        self.edit_cooldown = EDIT_COOLDOWN;
+
+        if breaking {
+            return self.set_block(hit.position, Block::AIR, BlockState::default());
+        }
+        // using a block that can be opened opens or closes it instead of placing a block against it
+        let target = self.world.get_block(hit.position).unwrap();
+        if self.world.registry().get(target).openable {
+            let mut state = self.world.block_state(hit.position).unwrap();
+            state.open = !state.open;
+            let changed = self.world.set_block_state(hit.position, state);
+            return self.remesh_blocks_now(&changed);
+        }
+        let position = hit.position + hit.normal.offset();
+        let replaceable = self.world.get_block(position).is_some_and(|block| {
+            block == Block::AIR || self.world.registry().get(block).fluid.is_some()
+        });
+        if !replaceable {
+            return Ok(());
+        }
+        let block = self.hotbar[self.selected_slot];
+        let definition = self.world.registry().get(block);
+        let hit_height = ray.at(hit.distance).y - position.y as f32;
+        let state = BlockState::placed(definition, ray.direction, hit_height);
+        // solid blocks can't be placed where they would trap the player
+        if !self.noclip && definition.solid {
+            let player_bounds = self.player.bounds();
+            let blocked = model::boxes(definition.model, state)
+                .into_iter()
+                .any(|model_box| {
+                    let (min, max) = model_box.at(position);
+                    Aabb { min, max }.intersects(player_bounds)
+                });
+            if blocked {
+                return Ok(());
+            }
+        }
+        self.set_block(position, block, state)
+    }
 
+    /// Replaces a block and remeshes the chunks that it shows up in right away,
+    /// so the change is drawn on the next frame
+    fn set_block(
+        &mut self,
+        position: cgmath::Vector3<i32>,
+        block: Block,
+        state: BlockState,
+    ) -> anyhow::Result<()> {
+        let changed = self.world.place_block(position, block, state);
+        self.remesh_blocks_now(&changed)
+    }
+
+    /// Remeshes the chunks that the blocks show up in right away, so the changes are drawn on the next frame
+    fn remesh_blocks_now(&mut self, changed: &[cgmath::Vector3<i32>]) -> anyhow::Result<()> {
+        for chunk_position in chunks_showing_blocks(changed) {
+            self.dirty_chunks.remove(&chunk_position);
+            self.remesh_chunk_now(chunk_position)?;
+        }
         Ok(())
     }
 
+    /// Rebuilds the mesh of the chunk on this thread instead of on the workers,
+    /// mesh jobs for the chunk that are still running are ignored when they finish
+    fn remesh_chunk_now(&mut self, chunk_position: cgmath::Vector3<i32>) -> anyhow::Result<()> {
+        self.workers
+            .cancel(|job| matches!(job, Job::Mesh(position, _) if *position == chunk_position));
+        self.mesh_versions.remove(&chunk_position);
+        let Some(faces) = self
+            .world
+            .generate_chunk_faces(chunk_position, MESHING_MODE)
+        else {
+            self.chunk_meshes.remove(&chunk_position);
+            return Ok(());
+        };
+        let (opaque_faces, translucent_faces) = mesh_faces(
+            self.world.registry(),
+            chunk_position,
+            self.world.chunk(chunk_position).unwrap(),
+            faces,
+        );
+        self.upload_chunk_mesh(chunk_position, &opaque_faces, translucent_faces)
+    }
+
+    fn upload_chunk_mesh(
+        &mut self,
+        chunk_position: cgmath::Vector3<i32>,
+        opaque_faces: &[Face],
+        translucent_faces: Vec<Face>,
+    ) -> anyhow::Result<()> {
+        if let Some(chunk_mesh) = self.chunk_meshes.get_mut(&chunk_position) {
+            chunk_mesh.opaque_faces.upload(
+                &self.device,
+                &self.queue,
+                &self.faces_bind_group_layout,
+                opaque_faces,
+            )?;
+            chunk_mesh.translucent_faces = translucent_faces;
+            chunk_mesh.translucent_faces_changed = true;
+        } else {
+            let chunk_mesh = ChunkMesh {
+                opaque_faces: DynamicFaces::new(
+                    &self.device,
+                    &self.faces_bind_group_layout,
+                    "Chunk Faces Storage Buffer",
+                    opaque_faces,
+                )?,
+                translucent_faces,
+                sorted_translucent_faces: None,
+                translucent_faces_changed: true,
+            };
+            self.chunk_meshes.insert(chunk_position, chunk_mesh);
+        }
+        Ok(())
+    }
+
+    /// Starts rebuilding the meshes of the chunks on the workers, the old meshes are drawn until the new ones
+    /// are done. The meshes of chunks that aren't loaded anymore are dropped
+    fn remesh_chunks(&mut self, chunk_positions: impl IntoIterator<Item = cgmath::Vector3<i32>>) {
+        for chunk_position in chunk_positions {
+            self.workers
+                .cancel(|job| matches!(job, Job::Mesh(position, _) if *position == chunk_position));
+            let Some(snapshot) = self.world.snapshot_chunk(chunk_position) else {
+                self.chunk_meshes.remove(&chunk_position);
+                self.mesh_versions.remove(&chunk_position);
+                continue;
+            };
+            let version = self.next_mesh_version;
+            self.next_mesh_version += 1;
+            self.mesh_versions.insert(chunk_position, version);
+
+            let registry = self.world.shared_registry();
+            let priority = self
+                .load_center
+                .map_or(0, |center| job_priority(center, chunk_position));
+            self.workers
+                .submit(Job::Mesh(chunk_position, version), priority, move || {
+                    let faces = snapshot.generate_faces(&registry, MESHING_MODE);
+                    let (opaque_faces, translucent_faces) =
+                        mesh_faces(&registry, chunk_position, &snapshot.chunk, faces);
+                    JobResult::Mesh {
+                        opaque_faces,
+                        translucent_faces,
+                    }
+                });
+        }
+    }
+
+    /// Waits for the chunks that were unloaded to be saved first, so the region files are only written by one
+    /// thread at a time
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        self.saver.wait();
+        self.receive_finished_saves();
+        self.world.save(SAVE_DIRECTORY)
+    }
+
     pub fn lost_focus(&mut self) {
         self.pressed_keys.clear();
+        self.release_cursor();
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -507,6 +1354,10 @@ impl Game {
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
 
+        self.sort_translucent_faces()?;
+        self.upload_falling_block_faces()?;
+        self.upload_outline()?;
+
         // Upload camera data
         {
             let mut buffer = UniformBuffer::new([0; Camera::SHADER_SIZE.get() as _]);
@@ -552,14 +1403,48 @@ impl Game {
 
             render_pass.set_pipeline(&self.render_pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
-            for face_info in &self.face_infos {
-                render_pass.set_bind_group(
-                    1,
-                    &self.vertices_faces_bind_group,
-                    &[face_info.start_offset],
-                );
-                render_pass.draw(0..6 * face_info.count, 0..1);
+            for chunk_mesh in self.chunk_meshes.values() {
+                render_pass.set_bind_group(1, &chunk_mesh.opaque_faces.bind_group, &[]);
+                render_pass.draw(0..6 * chunk_mesh.opaque_faces.count, 0..1);
             }
+
+            render_pass.set_bind_group(1, &self.falling_block_faces.bind_group, &[]);
+            render_pass.draw(0..6 * self.falling_block_faces.count, 0..1);
+
+            // the translucent falling block faces that are further away than a chunk are drawn before it
+            render_pass.set_pipeline(&self.translucent_render_pipeline);
+            let falling_faces = &self.falling_block_translucent_faces;
+            let mut drawn_falling_faces = 0u32;
+            for &(distance2, chunk_position) in &self.translucent_chunk_order {
+                let Some(sorted_faces) = self
+                    .chunk_meshes
+                    .get(&chunk_position)
+                    .and_then(|chunk_mesh| chunk_mesh.sorted_translucent_faces.as_ref())
+                else {
+                    continue;
+                };
+                let further = self.falling_block_translucent_distances
+                    [drawn_falling_faces as usize..]
+                    .iter()
+                    .take_while(|&&falling_distance2| falling_distance2 >= distance2)
+                    .count() as u32;
+                if further > 0 {
+                    render_pass.set_bind_group(1, &falling_faces.bind_group, &[]);
+                    render_pass.draw(
+                        6 * drawn_falling_faces..6 * (drawn_falling_faces + further),
+                        0..1,
+                    );
+                    drawn_falling_faces += further;
+                }
+                render_pass.set_bind_group(1, &sorted_faces.bind_group, &[]);
+                render_pass.draw(0..6 * sorted_faces.count, 0..1);
+            }
+            render_pass.set_bind_group(1, &falling_faces.bind_group, &[]);
+            render_pass.draw(6 * drawn_falling_faces..6 * falling_faces.count, 0..1);
+
+            render_pass.set_pipeline(&self.outline_render_pipeline);
+            render_pass.set_vertex_buffer(0, self.outline.vertex_buffer.slice(..));
+            render_pass.draw(0..self.outline.vertex_count, 0..1);
         }
         self.queue.submit([encoder.finish()]);
 
@@ -569,7 +1454,267 @@ impl Game {
         Ok(())
     }
 
+    /// Sorts the translucent faces of the chunks back to front from the camera so they blend correctly.
+    /// Only the chunks whose faces changed are sorted again, unless the camera moved further than
+    /// [`TRANSLUCENT_SORT_DISTANCE`] since the last time
+    fn sort_translucent_faces(&mut self) -> anyhow::Result<()> {
+        let camera_position: cgmath::Vector3<f32> =
+            Point::IDENTITY.transform(self.camera.transform).into();
+        let moved = self.translucent_sort_position.is_none_or(|position| {
+            (camera_position - position).magnitude2()
+                > TRANSLUCENT_SORT_DISTANCE * TRANSLUCENT_SORT_DISTANCE
+        });
+        if moved {
+            self.translucent_sort_position = Some(camera_position);
+        }
+        let sort_position = self.translucent_sort_position.unwrap();
+
+        let mut order_changed = moved;
+        for chunk_mesh in self.chunk_meshes.values_mut() {
+            if !moved && !chunk_mesh.translucent_faces_changed {
+                continue;
+            }
+            chunk_mesh.translucent_faces_changed = false;
+            order_changed = true;
+            let mut faces = chunk_mesh.translucent_faces.clone();
+            faces.sort_by(|a, b| {
+                face_distance2(b, sort_position).total_cmp(&face_distance2(a, sort_position))
+            });
+            match &mut chunk_mesh.sorted_translucent_faces {
+                Some(sorted_faces) => sorted_faces.upload(
+                    &self.device,
+                    &self.queue,
+                    &self.faces_bind_group_layout,
+                    &faces,
+                )?,
+                None if faces.is_empty() => {}
+                None => {
+                    chunk_mesh.sorted_translucent_faces = Some(DynamicFaces::new(
+                        &self.device,
+                        &self.faces_bind_group_layout,
+                        "Chunk Translucent Faces Storage Buffer",
+                        &faces,
+                    )?);
+                }
+            }
+        }
+
+        if order_changed {
+            let half_chunk = (CHUNK_SIZE as f32 - 1.0) / 2.0;
+            self.translucent_chunk_order = self
+                .chunk_meshes
+                .iter()
+                .filter(|(_, chunk_mesh)| !chunk_mesh.translucent_faces.is_empty())
+                .map(|(&chunk_position, _)| {
+                    let center = world::chunk_origin(chunk_position).cast().unwrap()
+                        + cgmath::vec3(half_chunk, half_chunk, half_chunk);
+                    ((center - sort_position).magnitude2(), chunk_position)
+                })
+                .collect();
+            self.translucent_chunk_order
+                .sort_by(|a, b| b.0.total_cmp(&a.0));
+        }
+        Ok(())
+    }
+
+    /// Uploads the faces of the falling blocks, which move between ticks so they change every frame
+    fn upload_falling_block_faces(&mut self) -> anyhow::Result<()> {
+        let mut translucent_faces = vec![];
+        let registry = self.world.registry();
+        let alpha = self.tick_time.as_secs_f32() / TICK_DURATION.as_secs_f32();
+        let mut falling_block_faces = vec![];
+        for falling_block in self.world.falling_blocks() {
+            let position = falling_block.render_position(alpha);
+            let block_position = position.map(|x| x.round() as i32);
+            let definition = registry.get(falling_block.block);
+            let biome = self.world.biome(block_position).unwrap_or(Biome::Plains);
+            let light = self
+                .world
+                .get_light(block_position)
+                .unwrap_or(Light::UNLOADED);
+            for (direction, normal) in FACE_NORMALS.into_iter().enumerate() {
+                let face = Face {
+                    position,
+                    size: cgmath::vec3(1.0, 1.0, 1.0),
+                    normal: normal.cast().unwrap(),
+                    color: definition
+                        .face_color(
+                            biome,
+                            BlockState::default(),
+                            Direction::from_offset(normal).unwrap(),
+                        )
+                        .extend(definition.alpha),
+                    light: cgmath::vec2(light.sky, light.block).cast().unwrap() / MAX_LIGHT as f32,
+                    ambient_occlusion: cgmath::vec4(1.0, 1.0, 1.0, 1.0),
+                    direction: direction as u32,
+                };
+                if definition.is_translucent() {
+                    translucent_faces.push(face);
+                } else {
+                    falling_block_faces.push(face);
+                }
+            }
+        }
+
+        let camera_position: cgmath::Vector3<f32> =
+            Point::IDENTITY.transform(self.camera.transform).into();
+        let mut translucent_faces = translucent_faces
+            .into_iter()
+            .map(|face| (face_distance2(&face, camera_position), face))
+            .collect::<Vec<_>>();
+        translucent_faces.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let (distances, translucent_faces): (Vec<_>, Vec<_>) =
+            translucent_faces.into_iter().unzip();
+        self.falling_block_translucent_distances = distances;
+
+        self.falling_block_translucent_faces.upload(
+            &self.device,
+            &self.queue,
+            &self.faces_bind_group_layout,
+            &translucent_faces,
+        )?;
+        self.falling_block_faces.upload(
+            &self.device,
+            &self.queue,
+            &self.faces_bind_group_layout,
+            &falling_block_faces,
+        )
+    }
+
+    /// Uploads the outline of the targeted block, it follows the boxes of the block's model
+    fn upload_outline(&mut self) -> anyhow::Result<()> {
+        let mut boxes = vec![];
+        if let Some(target) = self.target {
+            let block = self.world.get_block(target.position);
+            let state = self.world.block_state(target.position);
+            if let Some((block, state)) = block.zip(state) {
+                let model = self.world.registry().get(block).model;
+                for model_box in model::selection_boxes(model, state) {
+                    boxes.push(model_box.at(target.position));
+                }
+            }
+        }
+        self.outline.upload(&self.device, &self.queue, &boxes)
+    }
+
     pub fn window(&self) -> &Window {
         &self.window
     }
 }
+
+/// The chunks that contain the blocks, and the neighboring chunks whose faces can be hidden or shaded by the blocks
+fn chunks_showing_blocks(positions: &[cgmath::Vector3<i32>]) -> HashSet<cgmath::Vector3<i32>> {
+    let mut chunk_positions = HashSet::new();
+    for &position in positions {
+        let (chunk_position, local) = world::split_block_position(position);
+        // the offsets to the neighboring chunks along each axis that the block is next to
+        let offsets = [local.x, local.y, local.z].map(|local| match local {
+            0 => [0, -1],
+            local if local as usize == CHUNK_SIZE - 1 => [0, 1],
+            _ => [0, 0],
+        });
+        for x in offsets[0] {
+            for y in offsets[1] {
+                for z in offsets[2] {
+                    chunk_positions.insert(chunk_position + cgmath::vec3(x, y, z));
+                }
+            }
+        }
+    }
+    chunk_positions
+}
+
+/// The chunks and every chunk next to them, including diagonally. When a chunk is loaded or unloaded,
+/// its neighbors have faces on the border that are hidden or shaded differently now,
+/// and structures in new chunks can reach into them
+fn neighborhoods(
+    chunk_positions: impl IntoIterator<Item = cgmath::Vector3<i32>>,
+) -> HashSet<cgmath::Vector3<i32>> {
+    let mut neighborhoods = HashSet::new();
+    for chunk_position in chunk_positions {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    neighborhoods.insert(chunk_position + cgmath::vec3(x, y, z));
+                }
+            }
+        }
+    }
+    neighborhoods
+}
+
+/// The squared distance from the center of the face to `position`
+fn face_distance2(face: &Face, position: cgmath::Vector3<f32>) -> f32 {
+    let center = face.position - cgmath::vec3(0.5, 0.5, 0.5)
+        + (face.size + face.normal.mul_element_wise(face.size)) * 0.5;
+    (center - position).magnitude2()
+}
+
+/// Turns the faces of a chunk into the faces that the shader draws, split into opaque and translucent faces
+fn mesh_faces(
+    registry: &BlockRegistry,
+    chunk_position: cgmath::Vector3<i32>,
+    chunk: &Chunk,
+    faces: chunk::Faces,
+) -> (Vec<Face>, Vec<Face>) {
+    let origin = world::chunk_origin(chunk_position);
+
+    let mut opaque_faces = vec![];
+    let mut translucent_faces = vec![];
+    let mut push_face = |quad: chunk::Quad, direction: usize, color: cgmath::Vector3<f32>| {
+        let definition = registry.get(quad.block);
+        let (min, max) = quad.bounds.to_blocks();
+        let face = Face {
+            position: (origin + quad.position.cast().unwrap()).cast().unwrap() + min,
+            size: quad.size.cast().unwrap() - cgmath::vec3(1.0, 1.0, 1.0) + (max - min),
+            normal: face_normal(direction),
+            color: color.extend(definition.alpha),
+            light: cgmath::vec2(quad.light.sky, quad.light.block)
+                .cast()
+                .unwrap()
+                / MAX_LIGHT as f32,
+            ambient_occlusion: cgmath::Vector4::from(quad.ambient_occlusion)
+                .cast()
+                .unwrap()
+                / 3.0,
+            direction: direction as u32,
+        };
+        if definition.is_translucent() {
+            translucent_faces.push(face);
+        } else {
+            opaque_faces.push(face);
+        }
+    };
+
+    // in the same order as `FACE_NORMALS`
+    for (direction, quads) in [
+        faces.back,
+        faces.front,
+        faces.top,
+        faces.bottom,
+        faces.left,
+        faces.right,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let face_direction = Direction::from_offset(FACE_NORMALS[direction]).unwrap();
+        for quad in quads {
+            let color = registry.get(quad.block).face_color(
+                chunk.biome(quad.position.x, quad.position.z),
+                quad.state,
+                face_direction,
+            );
+            push_face(quad, direction, color);
+        }
+    }
+    for quad in faces.cross {
+        let color = registry
+            .get(quad.block)
+            .color(chunk.biome(quad.position.x, quad.position.z));
+        for direction in CROSS_DIRECTION..FACE_DIRECTIONS {
+            push_face(quad, direction, color);
+        }
+    }
+    (opaque_faces, translucent_faces)
+}
@@ -0,0 +1,366 @@
+use crate::{
+    biome::Biome,
+    chunk::{Block, Chunk, CHUNK_SIZE},
+    noise::{hash, hash_to_unit, FractalNoise, Random},
+    registry::BlockRegistry,
+    structure::Structure,
+    world::chunk_origin,
+};
+
+const BASE_HEIGHT: f64 = 0.0;
+const DIRT_DEPTH: i32 = 3;
+/// How far apart in climate space biomes blend their terrain shape
+const BIOME_BLEND_DISTANCE: f64 = 0.3;
+
+const TUNNEL_RADIUS: f64 = 0.06;
+const CAVERN_THRESHOLD: f64 = 0.3;
+const CAVERN_MIN_DEPTH: i32 = 12;
+const OVERHANG_THRESHOLD: f64 = 0.35;
+const OVERHANG_MAX_DEPTH: i32 = 6;
+const RAVINE_WIDTH: f64 = 0.025;
+const RAVINE_DEPTH: i32 = 40;
+
+/// The blocks that world generation places, looked up by name in the [`BlockRegistry`]
+#[derive(Debug, Clone, Copy)]
+pub struct GenerationBlocks {
+    pub stone: Block,
+    pub dirt: Block,
+    pub grass: Block,
+    pub sand: Block,
+    pub snow: Block,
+    pub log: Block,
+    pub leaves: Block,
+    pub cobblestone: Block,
+}
+
+impl GenerationBlocks {
+    pub fn resolve(registry: &BlockRegistry) -> anyhow::Result<Self> {
+        Ok(Self {
+            stone: registry.by_name("stone")?,
+            dirt: registry.by_name("dirt")?,
+            grass: registry.by_name("grass")?,
+            sand: registry.by_name("sand")?,
+            snow: registry.by_name("snow")?,
+            log: registry.by_name("log")?,
+            leaves: registry.by_name("leaves")?,
+            cobblestone: registry.by_name("cobblestone")?,
+        })
+    }
+}
+
+pub struct TerrainGenerator {
+    seed: u64,
+    blocks: GenerationBlocks,
+    height_noise: FractalNoise,
+    temperature_noise: FractalNoise,
+    humidity_noise: FractalNoise,
+    tunnel_noise: [FractalNoise; 2],
+    cavern_noise: FractalNoise,
+    overhang_noise: FractalNoise,
+    ravine_noise: FractalNoise,
+}
+
+impl TerrainGenerator {
+    pub fn new(seed: u64, blocks: GenerationBlocks) -> Self {
+        Self {
+            seed,
+            blocks,
+            height_noise: FractalNoise::new(hash(seed, &[0]), 5, 1.0 / 128.0),
+            tunnel_noise: [
+                FractalNoise::new(hash(seed, &[1]), 2, 1.0 / 48.0),
+                FractalNoise::new(hash(seed, &[2]), 2, 1.0 / 48.0),
+            ],
+            cavern_noise: FractalNoise::new(hash(seed, &[3]), 3, 1.0 / 64.0),
+            overhang_noise: FractalNoise::new(hash(seed, &[4]), 2, 1.0 / 24.0),
+            ravine_noise: FractalNoise::new(hash(seed, &[5]), 2, 1.0 / 256.0),
+            temperature_noise: FractalNoise::new(hash(seed, &[6]), 3, 1.0 / 512.0),
+            humidity_noise: FractalNoise::new(hash(seed, &[7]), 3, 1.0 / 512.0),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn blocks(&self) -> &GenerationBlocks {
+        &self.blocks
+    }
+
+    /// The `(temperature, humidity)` of a column, both are in the range `-1.0..=1.0`
+    pub fn climate(&self, x: i32, z: i32) -> (f64, f64) {
+        let (x, z) = (x as f64, z as f64);
+        (
+            (self.temperature_noise.sample2(x, z) * 2.0).clamp(-1.0, 1.0),
+            (self.humidity_noise.sample2(x, z) * 2.0).clamp(-1.0, 1.0),
+        )
+    }
+
+    /// How much each biome in [`Biome::ALL`] contributes to the terrain shape of a column,
+    /// the weights add up to 1 so that the terrain changes smoothly between biomes
+    fn biome_weights(&self, x: i32, z: i32) -> [f64; Biome::ALL.len()] {
+        let (temperature, humidity) = self.climate(x, z);
+        let mut weights = Biome::ALL.map(|biome| {
+            let (biome_temperature, biome_humidity) = biome.climate();
+            let distance_squared =
+                (temperature - biome_temperature).powi(2) + (humidity - biome_humidity).powi(2);
+            (-distance_squared / (BIOME_BLEND_DISTANCE * BIOME_BLEND_DISTANCE)).exp()
+        });
+        let total = weights.iter().sum::<f64>();
+        if total > 0.0 {
+            weights.iter_mut().for_each(|weight| *weight /= total);
+        } else {
+            weights = Biome::ALL.map(|_| 1.0 / Biome::ALL.len() as f64);
+        }
+        weights
+    }
+
+    /// The biome with the closest climate to the column
+    pub fn biome(&self, x: i32, z: i32) -> Biome {
+        let weights = self.biome_weights(x, z);
+        let (index, _) = weights
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap();
+        Biome::ALL[index]
+    }
+
+    /// The y coordinate of the topmost solid block in a column
+    pub fn height(&self, x: i32, z: i32) -> i32 {
+        let height_scale = self
+            .biome_weights(x, z)
+            .iter()
+            .zip(Biome::ALL)
+            .map(|(weight, biome)| weight * biome.height_scale())
+            .sum::<f64>();
+        let noise = self.height_noise.sample2(x as f64, z as f64);
+        (BASE_HEIGHT + noise * height_scale).floor() as i32
+    }
+
+    pub fn generate_chunk(&self, chunk_position: cgmath::Vector3<i32>) -> Chunk {
+        let mut chunk = self.generate_terrain(chunk_position);
+        self.carve_caves(chunk_position, &mut chunk);
+        chunk.compact();
+        chunk
+    }
+
+    /// The heightmap pass, fills the columns with stone and the surface blocks of their biome
+    pub fn generate_terrain(&self, chunk_position: cgmath::Vector3<i32>) -> Chunk {
+        let origin = chunk_origin(chunk_position);
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE as u8 {
+            for z in 0..CHUNK_SIZE as u8 {
+                let (world_x, world_z) = (origin.x + x as i32, origin.z + z as i32);
+                let height = self.height(world_x, world_z);
+                let biome = self.biome(world_x, world_z);
+                chunk.biomes[x as usize][z as usize] = biome;
+                for y in 0..CHUNK_SIZE as u8 {
+                    let depth = height - (origin.y + y as i32);
+                    let block = match depth {
+                        ..=-1 => Block::AIR,
+                        0 => biome.surface_block(&self.blocks),
+                        1..=DIRT_DEPTH => biome.filler_block(&self.blocks),
+                        _ => self.blocks.stone,
+                    };
+                    chunk.set_block(x, y, z, block);
+                }
+            }
+        }
+        chunk
+    }
+
+    /// Whether the carving pass removes the block at a world position, `depth` is
+    /// how far below the surface of its column the block is.
+    ///
+    /// This only depends on the world position so caves line up across chunk boundaries
+    pub fn is_carved(&self, x: i32, y: i32, z: i32, depth: i32) -> bool {
+        if depth < 0 {
+            return false;
+        }
+        let (x, y, z) = (x as f64, y as f64, z as f64);
+
+        // ravines are narrow cuts along the zero line of a 2d noise that close up towards the bottom
+        if depth < RAVINE_DEPTH {
+            let width = RAVINE_WIDTH * (1.0 - depth as f64 / RAVINE_DEPTH as f64);
+            if self.ravine_noise.sample2(x, z).abs() < width {
+                return true;
+            }
+        }
+
+        // overhangs are pockets carved just under the surface, leaving the top of the column floating
+        if (1..=OVERHANG_MAX_DEPTH).contains(&depth)
+            && self.overhang_noise.sample3(x, y * 2.0, z) > OVERHANG_THRESHOLD
+        {
+            return true;
+        }
+
+        // large caverns deep underground
+        if depth >= CAVERN_MIN_DEPTH && self.cavern_noise.sample3(x, y * 1.5, z) > CAVERN_THRESHOLD
+        {
+            return true;
+        }
+
+        // tunnels are where the zero surfaces of two 3d noises intersect
+        let [a, b] = &self.tunnel_noise;
+        a.sample3(x, y, z).abs() < TUNNEL_RADIUS && b.sample3(x, y, z).abs() < TUNNEL_RADIUS
+    }
+
+    /// The structures whose origin is inside of the chunk, with their world space origin.
+    ///
+    /// Structures can extend into neighboring chunks, so these need to be placed with [`World::place_structure`](crate::world::World::place_structure)
+    pub fn structures(
+        &self,
+        chunk_position: cgmath::Vector3<i32>,
+    ) -> Vec<(cgmath::Vector3<i32>, Structure)> {
+        let origin = chunk_origin(chunk_position);
+        let mut structures = vec![];
+        for x in 0..CHUNK_SIZE as i32 {
+            for z in 0..CHUNK_SIZE as i32 {
+                let (world_x, world_z) = (origin.x + x, origin.z + z);
+                let height = self.height(world_x, world_z);
+                if !(origin.y..origin.y + CHUNK_SIZE as i32).contains(&(height + 1)) {
+                    continue;
+                }
+
+                let biome = self.biome(world_x, world_z);
+                let column_hash = hash(self.seed, &[world_x as i64, world_z as i64, 8]);
+                if hash_to_unit(column_hash) >= biome.decoration_density() {
+                    continue;
+                }
+                // dont place structures over cave openings
+                if self.is_carved(world_x, height, world_z, 0) {
+                    continue;
+                }
+
+                let mut random = Random::new(column_hash);
+                structures.push((
+                    cgmath::vec3(world_x, height + 1, world_z),
+                    Structure::choose(biome, &mut random),
+                ));
+            }
+        }
+        structures
+    }
+
+    /// The carving pass, removes blocks for caves, ravines and overhangs
+    pub fn carve_caves(&self, chunk_position: cgmath::Vector3<i32>, chunk: &mut Chunk) {
+        let origin = chunk_origin(chunk_position);
+        for x in 0..CHUNK_SIZE as u8 {
+            for z in 0..CHUNK_SIZE as u8 {
+                let (world_x, world_z) = (origin.x + x as i32, origin.z + z as i32);
+                let height = self.height(world_x, world_z);
+                for y in 0..CHUNK_SIZE as u8 {
+                    let world_y = origin.y + y as i32;
+                    if self.is_carved(world_x, world_y, world_z, height - world_y) {
+                        chunk.set_block(x, y, z, Block::AIR);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::default_registry;
+    use std::collections::HashSet;
+
+    fn generator(seed: u64) -> TerrainGenerator {
+        TerrainGenerator::new(
+            seed,
+            GenerationBlocks::resolve(&default_registry()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn same_seed_generates_identical_chunks() {
+        let a = generator(1234);
+        let b = generator(1234);
+        for chunk_position in [
+            cgmath::vec3(0, 0, 0),
+            cgmath::vec3(-3, -1, 7),
+            cgmath::vec3(12, 1, -5),
+        ] {
+            assert!(
+                a.generate_chunk(chunk_position).blocks == b.generate_chunk(chunk_position).blocks
+            );
+        }
+    }
+
+    #[test]
+    fn different_seeds_generate_different_terrain() {
+        let a = generator(1);
+        let b = generator(2);
+        let differs =
+            (0..16).any(|x| (0..16).any(|z| a.height(x * 7, z * 7) != b.height(x * 7, z * 7)));
+        assert!(differs);
+    }
+
+    #[test]
+    fn columns_are_layered() {
+        let generator = generator(42);
+        for (x, z) in [(0, 0), (5, -9), (-100, 37)] {
+            let height = generator.height(x, z);
+            let biome = generator.biome(x, z);
+            let block_at = |y: i32| {
+                let (chunk_position, local) =
+                    crate::world::split_block_position(cgmath::vec3(x, y, z));
+                generator
+                    .generate_terrain(chunk_position)
+                    .get_block(local.x, local.y, local.z)
+                    .unwrap()
+            };
+            assert_eq!(block_at(height + 1), Block::AIR);
+            assert_eq!(block_at(height), biome.surface_block(generator.blocks()));
+            assert_eq!(block_at(height - 1), biome.filler_block(generator.blocks()));
+            assert_eq!(
+                block_at(height - DIRT_DEPTH),
+                biome.filler_block(generator.blocks())
+            );
+            assert_eq!(block_at(height - DIRT_DEPTH - 1), generator.blocks().stone);
+        }
+    }
+
+    #[test]
+    fn carving_only_depends_on_world_position() {
+        let generator = generator(7);
+        let mut carved = 0;
+        for chunk_position in [
+            cgmath::vec3(0, -1, 0),
+            cgmath::vec3(1, -1, 0),
+            cgmath::vec3(0, -2, -1),
+        ] {
+            let chunk = generator.generate_chunk(chunk_position);
+            let origin = chunk_origin(chunk_position);
+            for x in 0..CHUNK_SIZE as u8 {
+                for y in 0..CHUNK_SIZE as u8 {
+                    for z in 0..CHUNK_SIZE as u8 {
+                        let position = origin + cgmath::vec3(x, y, z).cast().unwrap();
+                        let depth = generator.height(position.x, position.z) - position.y;
+                        let is_carved =
+                            generator.is_carved(position.x, position.y, position.z, depth);
+                        assert_eq!(
+                            chunk.get_block(x, y, z) == Some(Block::AIR),
+                            depth < 0 || is_carved
+                        );
+                        carved += is_carved as usize;
+                    }
+                }
+            }
+        }
+        assert!(carved > 0);
+    }
+
+    #[test]
+    fn all_biomes_are_generated() {
+        let generator = generator(3);
+        let mut found = HashSet::new();
+        for x in -64..64 {
+            for z in -64..64 {
+                found.insert(generator.biome(x * 64, z * 64));
+            }
+        }
+        assert_eq!(found.len(), Biome::ALL.len());
+    }
+}
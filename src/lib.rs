@@ -1,11 +1,30 @@
+pub mod behavior;
+pub mod biome;
 pub mod chunk;
+pub mod entity;
+pub mod fluid;
 mod game;
+pub mod generation;
+pub mod light;
 pub mod math;
+pub mod model;
+pub mod noise;
+pub mod palette;
+pub mod player;
+pub mod raycast;
+pub mod region;
+pub mod registry;
+pub mod state;
+pub mod streaming;
+pub mod structure;
 pub mod texture;
+pub mod tick;
+pub mod worker;
+pub mod world;
 
 use game::Game;
 use winit::{
-    event::{Event, StartCause, WindowEvent},
+    event::{DeviceEvent, Event, StartCause, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
@@ -31,6 +50,10 @@ pub async fn run() -> anyhow::Result<()> {
             event: WindowEvent::CloseRequested,
             window_id,
         } if window_id == game.window().id() => {
+            if let Err(error) = game.save() {
+                eprintln!("failed to save the world: {error}");
+                eprintln!("{}", error.backtrace());
+            }
             elwt.exit();
         }
 
@@ -40,7 +63,9 @@ pub async fn run() -> anyhow::Result<()> {
         } if window_id == game.window().id() && !elwt.exiting() => match event.state {
             winit::event::ElementState::Pressed => {
                 if let winit::keyboard::PhysicalKey::Code(code) = event.physical_key {
-                    game.pressed_keys.insert(code);
+                    if game.pressed_keys.insert(code) {
+                        game.key_pressed(code);
+                    }
                 }
             }
 
@@ -51,6 +76,19 @@ pub async fn run() -> anyhow::Result<()> {
             }
         },
 
+        Event::WindowEvent {
+            event: WindowEvent::MouseInput { state, button, .. },
+            window_id,
+        } if window_id == game.window().id() && !elwt.exiting() => match state {
+            winit::event::ElementState::Pressed => {
+                game.mouse_pressed(button);
+            }
+
+            winit::event::ElementState::Released => {
+                game.pressed_buttons.remove(&button);
+            }
+        },
+
         Event::WindowEvent {
             event: WindowEvent::Focused(false),
             window_id,
@@ -58,9 +96,17 @@ pub async fn run() -> anyhow::Result<()> {
             game.lost_focus();
         }
 
+        Event::DeviceEvent {
+            event: DeviceEvent::MouseMotion { delta },
+            ..
+        } if !elwt.exiting() => {
+            game.mouse_moved(delta);
+        }
+
         Event::NewEvents(cause) => {
             if let StartCause::Init = cause {
                 game.window().set_visible(true);
+                game.grab_cursor();
             }
             let last_frame_time = frame_start_time;
             frame_start_time = std::time::Instant::now();
@@ -0,0 +1,524 @@
+use crate::{
+    chunk::{Block, Chunk, CHUNK_SIZE},
+    palette::{PaletteStorage, LEN},
+    registry::BlockRegistry,
+    world::{chunk_origin, split_block_position, DIRECTIONS, DOWN, UP},
+};
+use std::collections::{HashMap, VecDeque};
+
+/// The brightest light level, for both sky light and block light
+pub const MAX_LIGHT: u8 = 15;
+
+/// The light levels of a block, both are in the range `0..=MAX_LIGHT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Light {
+    /// Light coming from the sky, this travels straight down without getting dimmer
+    pub sky: u8,
+    /// Light coming from blocks with a [`light_emission`](crate::registry::BlockDefinition::light_emission)
+    pub block: u8,
+}
+
+impl Light {
+    /// What a block that is not loaded is lit with
+    pub const UNLOADED: Light = Light {
+        sky: MAX_LIGHT,
+        block: 0,
+    };
+
+    /// Packs both levels into a single byte, for storing in chunks
+    pub fn pack(self) -> u8 {
+        self.sky << 4 | self.block
+    }
+
+    pub fn unpack(packed: u8) -> Self {
+        Self {
+            sky: packed >> 4,
+            block: packed & 0xF,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Channel {
+    Sky,
+    Block,
+}
+
+/// Flood fills light through the loaded chunks, light doesn't spread into chunks that aren't loaded
+struct Lighting<'a> {
+    chunks: &'a mut HashMap<cgmath::Vector3<i32>, Chunk>,
+    registry: &'a BlockRegistry,
+    /// Every position whose light was changed, some of them more than once
+    changed: Vec<cgmath::Vector3<i32>>,
+}
+
+impl Lighting<'_> {
+    fn block(&self, position: cgmath::Vector3<i32>) -> Option<Block> {
+        let (chunk_position, local) = split_block_position(position);
+        self.chunks
+            .get(&chunk_position)?
+            .get_block(local.x, local.y, local.z)
+    }
+
+    fn get(&self, position: cgmath::Vector3<i32>, channel: Channel) -> Option<u8> {
+        let (chunk_position, local) = split_block_position(position);
+        let light = self
+            .chunks
+            .get(&chunk_position)?
+            .get_light(local.x, local.y, local.z)?;
+        Some(match channel {
+            Channel::Sky => light.sky,
+            Channel::Block => light.block,
+        })
+    }
+
+    fn set(&mut self, position: cgmath::Vector3<i32>, channel: Channel, level: u8) {
+        let (chunk_position, local) = split_block_position(position);
+        let chunk = self.chunks.get_mut(&chunk_position).unwrap();
+        let old = chunk.get_light(local.x, local.y, local.z).unwrap();
+        let mut light = old;
+        match channel {
+            Channel::Sky => light.sky = level,
+            Channel::Block => light.block = level,
+        }
+        if light != old {
+            chunk.set_light(local.x, local.y, local.z, light);
+            self.changed.push(position);
+        }
+    }
+
+    fn is_transparent(&self, block: Block) -> bool {
+        self.registry.get(block).transparent
+    }
+
+    /// The light a block has on its own, without any light spreading into it.
+    ///
+    /// The sky shines into every transparent block at the top of the loaded chunks
+    fn source(&self, position: cgmath::Vector3<i32>, channel: Channel) -> u8 {
+        let block = self.block(position).unwrap();
+        match channel {
+            Channel::Sky => {
+                let is_top = self.block(position + UP).is_none();
+                if is_top && self.is_transparent(block) {
+                    MAX_LIGHT
+                } else {
+                    0
+                }
+            }
+            Channel::Block => self.registry.get(block).light_emission,
+        }
+    }
+
+    /// Spreads light from a block to its neighbor in `direction` if that makes the neighbor brighter
+    fn spread_into(
+        &mut self,
+        channel: Channel,
+        position: cgmath::Vector3<i32>,
+        direction: cgmath::Vector3<i32>,
+        queue: &mut VecDeque<cgmath::Vector3<i32>>,
+    ) {
+        let neighbor = position + direction;
+        let level = spread(channel, direction, self.get(position, channel).unwrap());
+        let block = self.block(neighbor).unwrap();
+        if self.is_transparent(block) && level > self.get(neighbor, channel).unwrap() {
+            self.set(neighbor, channel, level);
+            queue.push_back(neighbor);
+        }
+    }
+
+    /// Spreads light from all the positions in the queue to their neighbors,
+    /// the light levels of the positions must already be set
+    fn propagate(&mut self, channel: Channel, mut queue: VecDeque<cgmath::Vector3<i32>>) {
+        while let Some(position) = queue.pop_front() {
+            let level = self.get(position, channel).unwrap();
+            if level <= 1 {
+                continue;
+            }
+            for direction in DIRECTIONS {
+                let neighbor = position + direction;
+                let Some(block) = self.block(neighbor) else {
+                    continue;
+                };
+                if !self.is_transparent(block) {
+                    continue;
+                }
+                let spread = spread(channel, direction, level);
+                if spread > self.get(neighbor, channel).unwrap() {
+                    self.set(neighbor, channel, spread);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+
+    /// Removes the light of the positions and all the light that came from them, then spreads
+    /// the remaining light back into the darkened area
+    fn relight(&mut self, channel: Channel, positions: &[cgmath::Vector3<i32>]) {
+        let mut removal = VecDeque::new();
+        for &position in positions {
+            let level = self.get(position, channel).unwrap();
+            if level > 0 {
+                self.set(position, channel, 0);
+                removal.push_back((position, level));
+            }
+        }
+
+        let mut queue = VecDeque::new();
+        while let Some((position, level)) = removal.pop_front() {
+            for direction in DIRECTIONS {
+                let neighbor = position + direction;
+                let Some(neighbor_level) = self.get(neighbor, channel) else {
+                    continue;
+                };
+                if neighbor_level == 0 {
+                    continue;
+                }
+                let came_from_position = neighbor_level < level
+                    || (channel == Channel::Sky
+                        && direction == DOWN
+                        && level == MAX_LIGHT
+                        && neighbor_level == MAX_LIGHT);
+                if came_from_position {
+                    self.set(neighbor, channel, 0);
+                    removal.push_back((neighbor, neighbor_level));
+                    let source = self.source(neighbor, channel);
+                    if source > 0 {
+                        self.set(neighbor, channel, source);
+                        queue.push_back(neighbor);
+                    }
+                } else {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+
+        for &position in positions {
+            let source = self.source(position, channel);
+            if source > self.get(position, channel).unwrap() {
+                self.set(position, channel, source);
+                queue.push_back(position);
+            }
+            for direction in DIRECTIONS {
+                let neighbor = position + direction;
+                if self.get(neighbor, channel).is_some_and(|level| level > 0) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        self.propagate(channel, queue);
+    }
+}
+
+/// Recalculates the light around blocks that have changed, all of the positions must be in loaded chunks.
+///
+/// Returns the positions whose light changed, which can contain duplicates
+pub fn relight_blocks(
+    chunks: &mut HashMap<cgmath::Vector3<i32>, Chunk>,
+    registry: &BlockRegistry,
+    positions: &[cgmath::Vector3<i32>],
+) -> Vec<cgmath::Vector3<i32>> {
+    let mut lighting = Lighting {
+        chunks,
+        registry,
+        changed: vec![],
+    };
+    lighting.relight(Channel::Sky, positions);
+    lighting.relight(Channel::Block, positions);
+    lighting.changed
+}
+
+/// Lights a chunk that was just loaded, spreading light into it from its neighbors and from it into them.
+///
+/// The light inside of the chunk is filled in first without looking at the other chunks,
+/// so only the light crossing the chunk borders has to go through the slower world lookups
+pub fn light_chunk(
+    chunks: &mut HashMap<cgmath::Vector3<i32>, Chunk>,
+    registry: &BlockRegistry,
+    chunk_position: cgmath::Vector3<i32>,
+) {
+    let chunk = &chunks[&chunk_position];
+    let transparent = chunk
+        .blocks
+        .iter()
+        .map(|block| registry.get(block).transparent)
+        .collect::<Vec<_>>();
+    let mut block_light = chunk
+        .blocks
+        .iter()
+        .map(|block| registry.get(block).light_emission)
+        .collect::<Vec<_>>();
+    let mut sky_light = vec![0; LEN];
+    let above = chunks.get(&(chunk_position + UP));
+    for x in 0..CHUNK_SIZE as u8 {
+        for z in 0..CHUNK_SIZE as u8 {
+            let mut incoming = match above {
+                Some(above) => match above.get_light(x, 0, z).unwrap().sky {
+                    MAX_LIGHT => MAX_LIGHT,
+                    _ => 0,
+                },
+                None => MAX_LIGHT,
+            };
+            for y in (0..CHUNK_SIZE as u8).rev() {
+                let index = Chunk::index(x, y, z);
+                if transparent[index] {
+                    sky_light[index] = incoming;
+                } else {
+                    incoming = 0;
+                }
+            }
+        }
+    }
+    fill_chunk(&mut sky_light, &transparent, Channel::Sky);
+    fill_chunk(&mut block_light, &transparent, Channel::Block);
+    chunks.get_mut(&chunk_position).unwrap().light = PaletteStorage::from_values(
+        sky_light
+            .into_iter()
+            .zip(block_light)
+            .map(|(sky, block)| Light { sky, block }.pack()),
+    );
+
+    let mut lighting = Lighting {
+        chunks,
+        registry,
+        changed: vec![],
+    };
+    for channel in [Channel::Sky, Channel::Block] {
+        let mut queue = VecDeque::new();
+        for direction in DIRECTIONS {
+            if !lighting.chunks.contains_key(&(chunk_position + direction)) {
+                continue;
+            }
+            for position in boundary_layer(chunk_position, direction) {
+                let neighbor = position + direction;
+                lighting.spread_into(channel, position, direction, &mut queue);
+                lighting.spread_into(channel, neighbor, -direction, &mut queue);
+            }
+        }
+        lighting.propagate(channel, queue);
+    }
+
+    // the top of the chunk below was lit by the sky, but now it is only lit by what comes through this chunk
+    let below = chunk_position + DOWN;
+    if lighting.chunks.contains_key(&below) {
+        let positions = boundary_layer(below, UP)
+            .filter(|&position| {
+                lighting.get(position, Channel::Sky) == Some(MAX_LIGHT)
+                    && lighting.get(position + UP, Channel::Sky) != Some(MAX_LIGHT)
+            })
+            .collect::<Vec<_>>();
+        lighting.relight(Channel::Sky, &positions);
+    }
+}
+
+/// Removes the light that was spreading from a chunk that was just unloaded into its neighbors,
+/// and lets the sky shine into the chunk below it again
+pub fn unlight_chunk(
+    chunks: &mut HashMap<cgmath::Vector3<i32>, Chunk>,
+    registry: &BlockRegistry,
+    chunk_position: cgmath::Vector3<i32>,
+) {
+    let mut positions = vec![];
+    for direction in DIRECTIONS {
+        let neighbor = chunk_position + direction;
+        if chunks.contains_key(&neighbor) {
+            positions.extend(boundary_layer(neighbor, -direction));
+        }
+    }
+    relight_blocks(chunks, registry, &positions);
+}
+
+/// How much light a block gets from its neighbor in the opposite direction
+fn spread(channel: Channel, direction: cgmath::Vector3<i32>, level: u8) -> u8 {
+    if channel == Channel::Sky && direction == DOWN && level == MAX_LIGHT {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Flood fills the light levels of a single chunk, indexed like the blocks of a [`Chunk`]
+fn fill_chunk(levels: &mut [u8], transparent: &[bool], channel: Channel) {
+    let size = CHUNK_SIZE as i32;
+    let mut queue = (0..LEN).filter(|&i| levels[i] > 1).collect::<VecDeque<_>>();
+    while let Some(index) = queue.pop_front() {
+        let position = cgmath::vec3(
+            index / (CHUNK_SIZE * CHUNK_SIZE),
+            index / CHUNK_SIZE % CHUNK_SIZE,
+            index % CHUNK_SIZE,
+        )
+        .cast::<i32>()
+        .unwrap();
+        for direction in DIRECTIONS {
+            let neighbor = position + direction;
+            if !(0..3).all(|axis| (0..size).contains(&neighbor[axis])) {
+                continue;
+            }
+            let neighbor_index = Chunk::index(neighbor.x as u8, neighbor.y as u8, neighbor.z as u8);
+            let level = spread(channel, direction, levels[index]);
+            if transparent[neighbor_index] && level > levels[neighbor_index] {
+                levels[neighbor_index] = level;
+                queue.push_back(neighbor_index);
+            }
+        }
+    }
+}
+
+/// The positions of the blocks in a chunk that touch the neighboring chunk in `direction`
+fn boundary_layer(
+    chunk_position: cgmath::Vector3<i32>,
+    direction: cgmath::Vector3<i32>,
+) -> impl Iterator<Item = cgmath::Vector3<i32>> {
+    let size = CHUNK_SIZE as i32;
+    chunk_positions(chunk_position).filter(move |position| {
+        let local = position - chunk_origin(chunk_position);
+        (0..3).all(|axis| match direction[axis] {
+            1 => local[axis] == size - 1,
+            -1 => local[axis] == 0,
+            _ => true,
+        })
+    })
+}
+
+fn chunk_positions(
+    chunk_position: cgmath::Vector3<i32>,
+) -> impl Iterator<Item = cgmath::Vector3<i32>> {
+    let origin = chunk_origin(chunk_position);
+    let size = CHUNK_SIZE as i32;
+    (0..size).flat_map(move |x| {
+        (0..size).flat_map(move |y| (0..size).map(move |z| origin + cgmath::vec3(x, y, z)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::default_registry;
+
+    fn light_at(
+        chunks: &HashMap<cgmath::Vector3<i32>, Chunk>,
+        position: cgmath::Vector3<i32>,
+    ) -> Light {
+        let (chunk_position, local) = split_block_position(position);
+        chunks[&chunk_position]
+            .get_light(local.x, local.y, local.z)
+            .unwrap()
+    }
+
+    /// Lights every chunk from scratch, for comparing against incremental updates
+    fn full_relight(
+        chunks: &HashMap<cgmath::Vector3<i32>, Chunk>,
+        registry: &BlockRegistry,
+    ) -> HashMap<cgmath::Vector3<i32>, Chunk> {
+        let mut relit = HashMap::new();
+        for (&chunk_position, chunk) in chunks {
+            let mut chunk = chunk.clone();
+            chunk.light = Chunk::new().light;
+            relit.insert(chunk_position, chunk);
+        }
+        let chunk_positions = relit.keys().copied().collect::<Vec<_>>();
+        for chunk_position in chunk_positions {
+            light_chunk(&mut relit, registry, chunk_position);
+        }
+        relit
+    }
+
+    fn set_block(
+        chunks: &mut HashMap<cgmath::Vector3<i32>, Chunk>,
+        registry: &BlockRegistry,
+        position: cgmath::Vector3<i32>,
+        block: Block,
+    ) {
+        let (chunk_position, local) = split_block_position(position);
+        chunks
+            .get_mut(&chunk_position)
+            .unwrap()
+            .set_block(local.x, local.y, local.z, block);
+        relight_blocks(chunks, registry, &[position]);
+    }
+
+    #[test]
+    fn enclosed_spaces_are_dark_and_lamps_light_them() {
+        let registry = default_registry();
+        let stone = registry.by_name("stone").unwrap();
+        let lamp = registry.by_name("lamp").unwrap();
+
+        // a stone box with a hollow inside, with open sky above it
+        let mut chunks = HashMap::new();
+        let mut chunk = Chunk::new();
+        for x in 2..10 {
+            for y in 2..10 {
+                for z in 2..10 {
+                    chunk.set_block(x, y, z, stone);
+                }
+            }
+        }
+        for x in 4..8 {
+            for y in 4..8 {
+                for z in 4..8 {
+                    chunk.set_block(x, y, z, Block::AIR);
+                }
+            }
+        }
+        chunks.insert(cgmath::vec3(0, 0, 0), chunk);
+        chunks.insert(cgmath::vec3(0, 1, 0), Chunk::new());
+        light_chunk(&mut chunks, &registry, cgmath::vec3(0, 0, 0));
+        light_chunk(&mut chunks, &registry, cgmath::vec3(0, 1, 0));
+
+        let inside = cgmath::vec3(5, 5, 5);
+        assert_eq!(light_at(&chunks, inside), Light::default());
+        assert_eq!(light_at(&chunks, cgmath::vec3(5, 12, 5)).sky, MAX_LIGHT);
+        // the sky light goes straight down next to the box, and gets dimmer underneath it
+        assert_eq!(light_at(&chunks, cgmath::vec3(0, 0, 5)).sky, MAX_LIGHT);
+        assert_eq!(light_at(&chunks, cgmath::vec3(5, 0, 5)).sky, MAX_LIGHT - 4);
+
+        let emission = registry.get(lamp).light_emission;
+        set_block(&mut chunks, &registry, cgmath::vec3(4, 4, 4), lamp);
+        assert_eq!(light_at(&chunks, inside).block, emission - 3);
+        assert_eq!(light_at(&chunks, cgmath::vec3(1, 1, 1)).block, 0);
+
+        // opening the box lets the sky in
+        set_block(&mut chunks, &registry, cgmath::vec3(5, 8, 5), Block::AIR);
+        set_block(&mut chunks, &registry, cgmath::vec3(5, 9, 5), Block::AIR);
+        assert_eq!(light_at(&chunks, inside).sky, MAX_LIGHT);
+
+        // and removing the lamp and closing it again makes it dark
+        set_block(&mut chunks, &registry, cgmath::vec3(4, 4, 4), Block::AIR);
+        set_block(&mut chunks, &registry, cgmath::vec3(5, 9, 5), stone);
+        assert_eq!(light_at(&chunks, inside), Light::default());
+    }
+
+    #[test]
+    fn incremental_updates_match_lighting_from_scratch() {
+        let registry = default_registry();
+        let stone = registry.by_name("stone").unwrap();
+        let glass = registry.by_name("glass").unwrap();
+        let lamp = registry.by_name("lamp").unwrap();
+
+        let mut chunks = HashMap::new();
+        for x in -1..1 {
+            for y in -1..1 {
+                chunks.insert(cgmath::vec3(x, y, 0), Chunk::new());
+            }
+        }
+        let chunk_positions = chunks.keys().copied().collect::<Vec<_>>();
+        for chunk_position in chunk_positions {
+            light_chunk(&mut chunks, &registry, chunk_position);
+        }
+
+        let mut random = crate::noise::Random::new(3);
+        for _ in 0..300 {
+            let position = cgmath::vec3(
+                random.range(-16..=15),
+                random.range(-16..=15),
+                random.range(0..=15),
+            );
+            let block = [Block::AIR, stone, stone, glass, lamp][random.range(0..=4) as usize];
+            set_block(&mut chunks, &registry, position, block);
+        }
+
+        let relit = full_relight(&chunks, &registry);
+        for (chunk_position, chunk) in &chunks {
+            assert!(chunk.light == relit[chunk_position].light);
+        }
+    }
+}
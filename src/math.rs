@@ -253,6 +253,16 @@ impl Motor {
         other.apply(self)
     }
 
+    /// The direction that the x axis points in after the motor is applied,
+    /// for the camera this is the direction it is looking in
+    pub fn forward(self) -> cgmath::Vector3<f32> {
+        let origin: cgmath::Vector3<f32> = Point::IDENTITY.transform(self).into();
+        let ahead: cgmath::Vector3<f32> = Point::from(cgmath::vec3(1.0, 0.0, 0.0))
+            .transform(self)
+            .into();
+        ahead - origin
+    }
+
     pub fn inverse(self) -> Self {
         Self {
             s: self.s,
@@ -0,0 +1,223 @@
+use crate::{
+    chunk::plane_axes,
+    registry::Model,
+    state::{BlockState, Direction, Half},
+};
+
+/// The number of steps that block models are measured in along each axis of a block
+pub const FULL_SIZE: u8 = 16;
+
+/// An axis aligned box inside of a block, in sixteenths of a block from the corner of the block
+/// with the smallest coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModelBox {
+    pub min: cgmath::Vector3<u8>,
+    pub max: cgmath::Vector3<u8>,
+}
+
+impl ModelBox {
+    pub const FULL: ModelBox = ModelBox {
+        min: cgmath::Vector3::new(0, 0, 0),
+        max: cgmath::Vector3::new(FULL_SIZE, FULL_SIZE, FULL_SIZE),
+    };
+
+    /// A box that fills the block up to `depth` sixteenths in from the side that `side` points to
+    pub fn against(side: Direction, depth: u8) -> Self {
+        let mut model_box = Self::FULL;
+        let axis = side.axis();
+        if side.is_positive() {
+            model_box.min[axis] = FULL_SIZE - depth;
+        } else {
+            model_box.max[axis] = depth;
+        }
+        model_box
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        let min = self.min.zip(other.min, u8::max);
+        Self {
+            min,
+            max: self.max.zip(other.max, u8::min).zip(min, u8::max),
+        }
+    }
+
+    /// Whether the face of the box that points in `side` lies on that side of the block
+    pub fn touches_side(self, side: Direction) -> bool {
+        if side.is_positive() {
+            self.max[side.axis()] == FULL_SIZE
+        } else {
+            self.min[side.axis()] == 0
+        }
+    }
+
+    /// Whether the box covers the whole block along the axis
+    pub fn is_full_along(self, axis: usize) -> bool {
+        self.min[axis] == 0 && self.max[axis] == FULL_SIZE
+    }
+
+    /// The box in blocks instead of sixteenths
+    pub fn to_blocks(self) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        let scale = |v: cgmath::Vector3<u8>| v.map(|x| x as f32 / FULL_SIZE as f32);
+        (scale(self.min), scale(self.max))
+    }
+
+    /// The corners of the box in the block at `position`, in the same space as block positions
+    pub fn at(
+        self,
+        position: cgmath::Vector3<i32>,
+    ) -> (cgmath::Vector3<f32>, cgmath::Vector3<f32>) {
+        // blocks are centered on their positions
+        let corner = position.cast::<f32>().unwrap() - cgmath::vec3(0.5, 0.5, 0.5);
+        let (min, max) = self.to_blocks();
+        (corner + min, corner + max)
+    }
+}
+
+/// The horizontal direction that a block faces, blocks that were placed without turning them face along +x
+fn horizontal_facing(state: BlockState) -> Direction {
+    if state.facing.axis() == 1 {
+        Direction::PositiveX
+    } else {
+        state.facing
+    }
+}
+
+/// The boxes that make up the model of a block, turned the way its state says.
+/// Cross models aren't made of boxes so they don't have any
+pub fn boxes(model: Model, state: BlockState) -> Vec<ModelBox> {
+    let (bottom, top) = match state.half {
+        Half::Bottom => (Direction::NegativeY, Direction::PositiveY),
+        Half::Top => (Direction::PositiveY, Direction::NegativeY),
+    };
+    // the back of blocks that face towards whoever placed them
+    let back = horizontal_facing(state).opposite();
+    match model {
+        Model::Cube => vec![ModelBox::FULL],
+        Model::Cross => vec![],
+        Model::Slab => vec![ModelBox::against(bottom, 8)],
+        Model::Stairs => vec![
+            ModelBox::against(bottom, 8),
+            ModelBox::against(top, 8).intersection(ModelBox::against(back, 8)),
+        ],
+        Model::Trapdoor if state.open => vec![ModelBox::against(back, 3)],
+        Model::Trapdoor => vec![ModelBox::against(bottom, 3)],
+        Model::Pane => {
+            let mut pane = ModelBox::FULL;
+            let axis = horizontal_facing(state).axis();
+            pane.min[axis] = 7;
+            pane.max[axis] = 9;
+            vec![pane]
+        }
+    }
+}
+
+/// The boxes that rays hit and that outline the block when it is targeted.
+/// Models that aren't made of boxes, like plants, use their whole block space
+pub fn selection_boxes(model: Model, state: BlockState) -> Vec<ModelBox> {
+    let boxes = boxes(model, state);
+    if boxes.is_empty() {
+        vec![ModelBox::FULL]
+    } else {
+        boxes
+    }
+}
+
+/// Which parts of one side of a block its model covers. Each row is a bit mask along the first plane axis of the
+/// side, with one row for each sixteenth along the second plane axis, see [`plane_axes`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SideCoverage([u16; FULL_SIZE as usize]);
+
+impl SideCoverage {
+    pub const FULL: SideCoverage = SideCoverage([u16::MAX; FULL_SIZE as usize]);
+    pub const EMPTY: SideCoverage = SideCoverage([0; FULL_SIZE as usize]);
+
+    pub fn of(model: Model, state: BlockState, side: Direction) -> Self {
+        match model {
+            Model::Cube => return Self::FULL,
+            Model::Cross => return Self::EMPTY,
+            _ => {}
+        }
+        let (u_axis, v_axis) = plane_axes(side.axis());
+        let mut coverage = Self::EMPTY;
+        for model_box in boxes(model, state) {
+            if model_box.touches_side(side) {
+                let mask = Self::mask(model_box.min[u_axis], model_box.max[u_axis]);
+                for row in
+                    &mut coverage.0[model_box.min[v_axis] as usize..model_box.max[v_axis] as usize]
+                {
+                    *row |= mask;
+                }
+            }
+        }
+        coverage
+    }
+
+    fn mask(min: u8, max: u8) -> u16 {
+        ((1u32 << max) - (1u32 << min)) as u16
+    }
+
+    /// Whether the face of the box on the side that is along `axis` is completely covered
+    pub fn covers(self, model_box: ModelBox, axis: usize) -> bool {
+        let (u_axis, v_axis) = plane_axes(axis);
+        let mask = Self::mask(model_box.min[u_axis], model_box.max[u_axis]);
+        self.0[model_box.min[v_axis] as usize..model_box.max[v_axis] as usize]
+            .iter()
+            .all(|&row| row & mask == mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn models_only_cover_the_sides_they_touch() {
+        let stairs = BlockState {
+            facing: Direction::NegativeX,
+            ..BlockState::default()
+        };
+        let full_side = ModelBox::FULL;
+        let lower_half = ModelBox::against(Direction::NegativeY, 8);
+        let upper_half = ModelBox::against(Direction::PositiveY, 8);
+
+        // the tall back of the stairs is on the side opposite to the one it faces
+        let back = SideCoverage::of(Model::Stairs, stairs, Direction::PositiveX);
+        assert_eq!(back, SideCoverage::FULL);
+        let front = SideCoverage::of(Model::Stairs, stairs, Direction::NegativeX);
+        assert!(front.covers(lower_half, 0));
+        assert!(!front.covers(upper_half, 0));
+        let side = SideCoverage::of(Model::Stairs, stairs, Direction::PositiveZ);
+        assert!(side.covers(lower_half, 2));
+        assert!(!side.covers(full_side, 2));
+        assert!(side.covers(
+            lower_half.intersection(ModelBox::against(Direction::PositiveX, 8)),
+            2
+        ));
+
+        let top_slab = BlockState {
+            half: Half::Top,
+            ..BlockState::default()
+        };
+        assert_eq!(
+            SideCoverage::of(Model::Slab, top_slab, Direction::NegativeY),
+            SideCoverage::EMPTY
+        );
+        assert!(
+            SideCoverage::of(Model::Slab, top_slab, Direction::PositiveX).covers(upper_half, 0)
+        );
+
+        let open_trapdoor = BlockState {
+            facing: Direction::PositiveZ,
+            open: true,
+            ..BlockState::default()
+        };
+        assert_eq!(
+            boxes(Model::Trapdoor, open_trapdoor),
+            vec![ModelBox::against(Direction::NegativeZ, 3)]
+        );
+        assert_eq!(
+            selection_boxes(Model::Cross, BlockState::default()),
+            vec![ModelBox::FULL]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+const GOLDEN_GAMMA: u64 = 0x9E3779B97F4A7C15;
+
+fn mix(mut x: u64) -> u64 {
+    // splitmix64 finalizer
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
+}
+
+/// Mixes a seed with a list of values into a well distributed hash, this is the
+/// source of all randomness in world generation so that it is reproducible from a seed
+pub fn hash(seed: u64, values: &[i64]) -> u64 {
+    let mut result = mix(seed.wrapping_add(GOLDEN_GAMMA));
+    for &value in values {
+        result = mix(result ^ mix(value as u64).wrapping_add(GOLDEN_GAMMA));
+    }
+    result
+}
+
+/// Returns a value in the range `0.0..1.0` from a hash
+pub fn hash_to_unit(hash: u64) -> f64 {
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// A small deterministic random number generator, usually seeded from a [`hash`]
+#[derive(Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        mix(self.state)
+    }
+
+    /// Returns a value in the range `0.0..1.0`
+    pub fn next_f64(&mut self) -> f64 {
+        hash_to_unit(self.next_u64())
+    }
+
+    pub fn range(&mut self, range: std::ops::RangeInclusive<i32>) -> i32 {
+        let length = (*range.end() as i64 - *range.start() as i64 + 1) as u64;
+        (*range.start() as i64 + (self.next_u64() % length) as i64) as i32
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Seeded gradient noise, all the samples are in the range `-1.0..=1.0`
+#[derive(Clone, Copy)]
+pub struct Noise {
+    seed: u64,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    fn gradient2(&self, x: i64, z: i64, dx: f64, dz: f64) -> f64 {
+        const D: f64 = std::f64::consts::FRAC_1_SQRT_2;
+        const GRADIENTS: [(f64, f64); 8] = [
+            (1.0, 0.0),
+            (-1.0, 0.0),
+            (0.0, 1.0),
+            (0.0, -1.0),
+            (D, D),
+            (-D, D),
+            (D, -D),
+            (-D, -D),
+        ];
+        let (gx, gz) = GRADIENTS[(hash(self.seed, &[x, z]) % 8) as usize];
+        gx * dx + gz * dz
+    }
+
+    fn gradient3(&self, x: i64, y: i64, z: i64, dx: f64, dy: f64, dz: f64) -> f64 {
+        const GRADIENTS: [(f64, f64, f64); 12] = [
+            (1.0, 1.0, 0.0),
+            (-1.0, 1.0, 0.0),
+            (1.0, -1.0, 0.0),
+            (-1.0, -1.0, 0.0),
+            (1.0, 0.0, 1.0),
+            (-1.0, 0.0, 1.0),
+            (1.0, 0.0, -1.0),
+            (-1.0, 0.0, -1.0),
+            (0.0, 1.0, 1.0),
+            (0.0, -1.0, 1.0),
+            (0.0, 1.0, -1.0),
+            (0.0, -1.0, -1.0),
+        ];
+        let (gx, gy, gz) = GRADIENTS[(hash(self.seed, &[x, y, z]) % 12) as usize];
+        gx * dx + gy * dy + gz * dz
+    }
+
+    pub fn sample2(&self, x: f64, z: f64) -> f64 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (dx, dz) = (x - x0, z - z0);
+        let (x0, z0) = (x0 as i64, z0 as i64);
+
+        let (u, v) = (fade(dx), fade(dz));
+        let result = lerp(
+            lerp(
+                self.gradient2(x0, z0, dx, dz),
+                self.gradient2(x0 + 1, z0, dx - 1.0, dz),
+                u,
+            ),
+            lerp(
+                self.gradient2(x0, z0 + 1, dx, dz - 1.0),
+                self.gradient2(x0 + 1, z0 + 1, dx - 1.0, dz - 1.0),
+                u,
+            ),
+            v,
+        );
+        (result * std::f64::consts::SQRT_2).clamp(-1.0, 1.0)
+    }
+
+    pub fn sample3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (dx, dy, dz) = (x - x0, y - y0, z - z0);
+        let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+
+        let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+        let corner = |ox: i64, oy: i64, oz: i64| {
+            self.gradient3(
+                x0 + ox,
+                y0 + oy,
+                z0 + oz,
+                dx - ox as f64,
+                dy - oy as f64,
+                dz - oz as f64,
+            )
+        };
+        let result = lerp(
+            lerp(
+                lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+                lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+                v,
+            ),
+            lerp(
+                lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+                lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+                v,
+            ),
+            w,
+        );
+        result.clamp(-1.0, 1.0)
+    }
+}
+
+/// Layers multiple octaves of [`Noise`], each octave gets its own seed
+#[derive(Clone)]
+pub struct FractalNoise {
+    octaves: Vec<Noise>,
+    frequency: f64,
+    persistence: f64,
+    lacunarity: f64,
+}
+
+impl FractalNoise {
+    pub fn new(seed: u64, octaves: u32, frequency: f64) -> Self {
+        Self {
+            octaves: (0..octaves)
+                .map(|octave| Noise::new(hash(seed, &[octave as i64])))
+                .collect(),
+            frequency,
+            persistence: 0.5,
+            lacunarity: 2.0,
+        }
+    }
+
+    fn layer(&self, mut sample: impl FnMut(&Noise, f64) -> f64) -> f64 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut max_amplitude = 0.0;
+        let mut frequency = self.frequency;
+        for noise in &self.octaves {
+            total += sample(noise, frequency) * amplitude;
+            max_amplitude += amplitude;
+            amplitude *= self.persistence;
+            frequency *= self.lacunarity;
+        }
+        total / max_amplitude
+    }
+
+    pub fn sample2(&self, x: f64, z: f64) -> f64 {
+        self.layer(|noise, frequency| noise.sample2(x * frequency, z * frequency))
+    }
+
+    pub fn sample3(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.layer(|noise, frequency| noise.sample3(x * frequency, y * frequency, z * frequency))
+    }
+}
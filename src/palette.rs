@@ -0,0 +1,280 @@
+use crate::chunk::CHUNK_SIZE;
+
+/// The number of values stored in a [`PaletteStorage`], one for every block in a chunk
+pub const LEN: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Stores a value for every block in a chunk as indices into a palette of the distinct values,
+/// each index uses only as many bits as the palette needs.
+///
+/// Chunks made of a single value (like all air or all stone) don't store any indices at all
+#[derive(Debug, Clone)]
+pub enum PaletteStorage<T> {
+    Single(T),
+    Paletted {
+        palette: Vec<T>,
+        bits: u32,
+        data: Box<[u64]>,
+    },
+}
+
+fn bits_for_palette_len(len: usize) -> u32 {
+    // the bit widths always divide 64 so indices never straddle two words
+    [1, 2, 4, 8, 16]
+        .into_iter()
+        .find(|&bits| len <= 1 << bits)
+        .expect("a palette can't have more distinct values than there are entries")
+}
+
+fn get_index(data: &[u64], bits: u32, index: usize) -> usize {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) as u32 * bits;
+    ((data[index / per_word] >> shift) & ((1 << bits) - 1)) as usize
+}
+
+fn set_index(data: &mut [u64], bits: u32, index: usize, value: usize) {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) as u32 * bits;
+    let mask = ((1u64 << bits) - 1) << shift;
+    let word = &mut data[index / per_word];
+    *word = (*word & !mask) | ((value as u64) << shift);
+}
+
+fn packed_data(bits: u32, mut index: impl FnMut(usize) -> usize) -> Box<[u64]> {
+    let mut data = vec![0; LEN * bits as usize / 64].into_boxed_slice();
+    for i in 0..LEN {
+        set_index(&mut data, bits, i, index(i));
+    }
+    data
+}
+
+impl<T: Copy + Eq> PaletteStorage<T> {
+    pub fn new(value: T) -> Self {
+        Self::Single(value)
+    }
+
+    /// Builds the storage from the values in index order, `values` must yield [`LEN`] values
+    pub fn from_values(values: impl IntoIterator<Item = T>) -> Self {
+        let values = values.into_iter().collect::<Vec<_>>();
+        assert_eq!(values.len(), LEN);
+
+        let mut palette = vec![];
+        let indices = values
+            .iter()
+            .map(|value| match palette.iter().position(|v| v == value) {
+                Some(index) => index,
+                None => {
+                    palette.push(*value);
+                    palette.len() - 1
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if palette.len() == 1 {
+            return Self::Single(palette[0]);
+        }
+        let bits = bits_for_palette_len(palette.len());
+        Self::Paletted {
+            data: packed_data(bits, |i| indices[i]),
+            palette,
+            bits,
+        }
+    }
+
+    /// Builds paletted storage from its raw parts, returns `None` if the parts are inconsistent
+    pub fn from_raw_parts(palette: Vec<T>, bits: u32, data: Box<[u64]>) -> Option<Self> {
+        if palette.is_empty() || ![1, 2, 4, 8, 16].contains(&bits) {
+            return None;
+        }
+        if palette.len() > 1 << bits || data.len() != LEN * bits as usize / 64 {
+            return None;
+        }
+        if (0..LEN).any(|i| get_index(&data, bits, i) >= palette.len()) {
+            return None;
+        }
+        Some(Self::Paletted {
+            palette,
+            bits,
+            data,
+        })
+    }
+
+    pub fn get(&self, index: usize) -> T {
+        assert!(index < LEN);
+        match self {
+            Self::Single(value) => *value,
+            Self::Paletted {
+                palette,
+                bits,
+                data,
+            } => palette[get_index(data, *bits, index)],
+        }
+    }
+
+    pub fn set(&mut self, index: usize, value: T) {
+        assert!(index < LEN);
+        match self {
+            Self::Single(current) => {
+                if *current != value {
+                    let current = *current;
+                    *self = Self::Paletted {
+                        palette: vec![current, value],
+                        bits: 1,
+                        data: packed_data(1, |i| (i == index) as usize),
+                    };
+                }
+            }
+
+            Self::Paletted {
+                palette,
+                bits,
+                data,
+            } => {
+                if let Some(palette_index) = palette.iter().position(|&v| v == value) {
+                    set_index(data, *bits, index, palette_index);
+                } else if palette.len() < 1 << *bits {
+                    palette.push(value);
+                    set_index(data, *bits, index, palette.len() - 1);
+                } else {
+                    // try to make space by removing unused values before widening the indices
+                    self.compact();
+                    if let Self::Paletted {
+                        palette,
+                        bits,
+                        data,
+                    } = self
+                    {
+                        if palette.len() == 1 << *bits {
+                            let new_bits = bits_for_palette_len(palette.len() + 1);
+                            *data = packed_data(new_bits, |i| get_index(data, *bits, i));
+                            *bits = new_bits;
+                        }
+                    }
+                    self.set(index, value);
+                }
+            }
+        }
+    }
+
+    /// Removes values from the palette that are no longer used, and narrows the indices if possible.
+    /// If only one value is left this switches to [`PaletteStorage::Single`]
+    pub fn compact(&mut self) {
+        let Self::Paletted {
+            palette,
+            bits,
+            data,
+        } = self
+        else {
+            return;
+        };
+
+        let mut used = vec![false; palette.len()];
+        for i in 0..LEN {
+            used[get_index(data, *bits, i)] = true;
+        }
+        if used.iter().all(|&used| used) && bits_for_palette_len(palette.len()) == *bits {
+            return;
+        }
+
+        let mut new_palette = vec![];
+        let remap = palette
+            .iter()
+            .zip(&used)
+            .map(|(&value, &used)| {
+                used.then(|| {
+                    new_palette.push(value);
+                    new_palette.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if new_palette.len() == 1 {
+            *self = Self::Single(new_palette[0]);
+            return;
+        }
+        let new_bits = bits_for_palette_len(new_palette.len());
+        *data = packed_data(new_bits, |i| remap[get_index(data, *bits, i)].unwrap());
+        *bits = new_bits;
+        *palette = new_palette;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..LEN).map(|i| self.get(i))
+    }
+
+    /// The distinct values, this can contain values that are no longer used until [`PaletteStorage::compact`] is called
+    pub fn palette(&self) -> &[T] {
+        match self {
+            Self::Single(value) => std::slice::from_ref(value),
+            Self::Paletted { palette, .. } => palette,
+        }
+    }
+
+    /// How many bits are used to store each index into the palette
+    pub fn bits_per_entry(&self) -> u32 {
+        match self {
+            Self::Single(_) => 0,
+            Self::Paletted { bits, .. } => *bits,
+        }
+    }
+}
+
+impl<T: Copy + Eq> PartialEq for PaletteStorage<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Copy + Eq> Eq for PaletteStorage<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::Random;
+
+    #[test]
+    fn random_edits_round_trip() {
+        for (seed, distinct_values) in [(0, 2), (1, 3), (2, 17), (3, 300), (4, LEN as i32)] {
+            let mut random = Random::new(seed);
+            let mut storage = PaletteStorage::new(0u16);
+            let mut expected = vec![0u16; LEN];
+            for _ in 0..20000 {
+                let index = random.range(0..=LEN as i32 - 1) as usize;
+                let value = random.range(0..=distinct_values - 1) as u16;
+                storage.set(index, value);
+                expected[index] = value;
+            }
+            assert!(storage.iter().eq(expected.iter().copied()));
+
+            storage.compact();
+            assert!(storage.iter().eq(expected.iter().copied()));
+            assert!(PaletteStorage::from_values(expected.iter().copied()) == storage);
+        }
+    }
+
+    #[test]
+    fn filling_with_one_value_compacts_to_single() {
+        let mut storage = PaletteStorage::new(0u16);
+        for i in 0..LEN {
+            storage.set(i, (i % 7) as u16);
+        }
+        assert_eq!(storage.bits_per_entry(), 4);
+        for i in 0..LEN {
+            storage.set(i, 3);
+        }
+        storage.compact();
+        assert!(matches!(storage, PaletteStorage::Single(3)));
+        assert_eq!(storage.bits_per_entry(), 0);
+    }
+
+    #[test]
+    fn unused_values_are_reused_before_growing() {
+        let mut storage = PaletteStorage::new(0u16);
+        for value in 1..100 {
+            for i in 0..LEN {
+                storage.set(i, value);
+            }
+        }
+        assert!(storage.palette().len() <= 2);
+        assert_eq!(storage.bits_per_entry(), 1);
+    }
+}
@@ -0,0 +1,286 @@
+use cgmath::InnerSpace;
+
+use crate::{
+    model::{self, ModelBox},
+    world::World,
+};
+
+/// How wide the player is along the x and z axes, in blocks
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+/// How far above the bottom of the player the camera is
+pub const EYE_HEIGHT: f32 = 1.62;
+/// The highest ledge that the player walks up onto without jumping, enough for slabs but not for full blocks
+pub const STEP_HEIGHT: f32 = 0.6;
+/// In blocks per second
+pub const WALK_SPEED: f32 = 4.3;
+/// How much faster the player falls every second, in blocks per second
+pub const GRAVITY: f32 = 32.0;
+/// The upwards speed at the start of a jump, in blocks per second. High enough to jump onto a block
+pub const JUMP_SPEED: f32 = 9.0;
+/// In blocks per second
+pub const MAX_FALL_SPEED: f32 = 60.0;
+
+/// Boxes that are closer than this are treated as touching, so rounding errors don't let the player
+/// sink into the ground or get stuck on the blocks next to the ones it stands on
+const EPSILON: f32 = 1e-4;
+
+/// An axis aligned bounding box, in the same space as block positions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: cgmath::Vector3<f32>,
+    pub max: cgmath::Vector3<f32>,
+}
+
+impl Aabb {
+    pub fn translate(self, offset: cgmath::Vector3<f32>) -> Self {
+        Self {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Whether the boxes overlap by more than a rounding error, boxes that only touch don't
+    pub fn intersects(self, other: Self) -> bool {
+        (0..3).all(|axis| self.overlaps_along(other, axis))
+    }
+
+    fn overlaps_along(self, other: Self, axis: usize) -> bool {
+        self.min[axis] < other.max[axis] - EPSILON && self.max[axis] > other.min[axis] + EPSILON
+    }
+
+    /// How far the box can move along `axis`, up to `distance`, before it runs into one of the obstacles.
+    /// Obstacles that the box already overlaps are ignored so it can always get out of them
+    fn sweep(self, axis: usize, distance: f32, obstacles: &[Aabb]) -> f32 {
+        let mut allowed = distance;
+        for &obstacle in obstacles {
+            let in_the_way = (0..3)
+                .filter(|&other_axis| other_axis != axis)
+                .all(|other_axis| self.overlaps_along(obstacle, other_axis));
+            if !in_the_way {
+                continue;
+            }
+            if distance > 0.0 && obstacle.min[axis] >= self.max[axis] - EPSILON {
+                allowed = allowed.min((obstacle.min[axis] - self.max[axis]).max(0.0));
+            } else if distance < 0.0 && obstacle.max[axis] <= self.min[axis] + EPSILON {
+                allowed = allowed.max((obstacle.max[axis] - self.min[axis]).min(0.0));
+            }
+        }
+        allowed
+    }
+}
+
+/// The boxes of the solid blocks that overlap `area`, following the shapes of their models.
+/// Blocks in chunks that aren't loaded are solid, so nothing falls out of the loaded part of the world
+pub fn solid_boxes(world: &World, area: Aabb) -> Vec<Aabb> {
+    // blocks are centered on their positions, so the block that contains a point is the one it rounds to
+    let min = area.min.map(|x| (x + 0.5).floor() as i32);
+    let max = area.max.map(|x| (x + 0.5).floor() as i32);
+    let mut boxes = vec![];
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let position = cgmath::vec3(x, y, z);
+                let model_boxes = match world.get_block(position) {
+                    Some(block) => {
+                        let definition = world.registry().get(block);
+                        if !definition.solid {
+                            continue;
+                        }
+                        let state = world.block_state(position).unwrap_or_default();
+                        model::boxes(definition.model, state)
+                    }
+                    None => vec![ModelBox::FULL],
+                };
+                for model_box in model_boxes {
+                    let (min, max) = model_box.at(position);
+                    boxes.push(Aabb { min, max });
+                }
+            }
+        }
+    }
+    boxes
+}
+
+/// A player that walks around the world and collides with solid blocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    /// The center of the bottom of the player's bounding box
+    pub position: cgmath::Vector3<f32>,
+    /// In blocks per second
+    pub velocity: cgmath::Vector3<f32>,
+    /// Whether the player was standing on something at the end of the last step
+    pub on_ground: bool,
+}
+
+impl Player {
+    pub fn new(position: cgmath::Vector3<f32>) -> Self {
+        Self {
+            position,
+            velocity: cgmath::vec3(0.0, 0.0, 0.0),
+            on_ground: false,
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let half_width = PLAYER_WIDTH / 2.0;
+        Aabb {
+            min: self.position - cgmath::vec3(half_width, 0.0, half_width),
+            max: self.position + cgmath::vec3(half_width, PLAYER_HEIGHT, half_width),
+        }
+    }
+
+    pub fn eye_position(&self) -> cgmath::Vector3<f32> {
+        self.position + cgmath::vec3(0.0, EYE_HEIGHT, 0.0)
+    }
+
+    /// Moves the player for `dt` seconds. `walk` is the velocity that the player walks with along the x and z
+    /// axes, in blocks per second, and jumping only works while the player stands on something.
+    ///
+    /// Collisions are resolved one axis at a time, so walking into a wall at an angle slides along it.
+    /// Walking into a ledge that is at most [`STEP_HEIGHT`] high steps up onto it
+    pub fn step(&mut self, world: &World, walk: cgmath::Vector2<f32>, jump: bool, dt: f32) {
+        if jump && self.on_ground {
+            self.velocity.y = JUMP_SPEED;
+        }
+        self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-MAX_FALL_SPEED);
+        self.velocity.x = walk.x;
+        self.velocity.z = walk.y;
+
+        let offset = walk * dt;
+        let start = self.position;
+        let walked = self.walk(world, offset);
+        if self.on_ground && walked != offset {
+            let mut stepped = self.clone();
+            stepped.position = start;
+            let up = stepped.move_along(world, 1, STEP_HEIGHT);
+            let stepped_walked = stepped.walk(world, offset);
+            stepped.move_along(world, 1, -up);
+            if stepped_walked.magnitude2() > walked.magnitude2() + EPSILON {
+                self.position = stepped.position;
+            }
+        }
+
+        let fall = self.velocity.y * dt;
+        let fallen = self.move_along(world, 1, fall);
+        self.on_ground = fall < 0.0 && fallen > fall;
+        if fallen != fall {
+            self.velocity.y = 0.0;
+        }
+    }
+
+    /// Moves along the x axis and then along the z axis, returns how far the player got
+    fn walk(&mut self, world: &World, offset: cgmath::Vector2<f32>) -> cgmath::Vector2<f32> {
+        cgmath::vec2(
+            self.move_along(world, 0, offset.x),
+            self.move_along(world, 2, offset.y),
+        )
+    }
+
+    /// Returns how far the player got before it hit something
+    fn move_along(&mut self, world: &World, axis: usize, distance: f32) -> f32 {
+        if distance == 0.0 {
+            return 0.0;
+        }
+        let bounds = self.bounds();
+        let mut offset = cgmath::vec3(0.0, 0.0, 0.0);
+        offset[axis] = distance;
+        let swept = bounds.translate(offset);
+        let area = Aabb {
+            min: bounds.min.zip(swept.min, f32::min),
+            max: bounds.max.zip(swept.max, f32::max),
+        };
+        let moved = bounds.sweep(axis, distance, &solid_boxes(world, area));
+        self.position[axis] += moved;
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A world with a stone floor with its top at y = 0.5, a stone wall along x = 8,
+    /// a slab at (3, 1, 10) and a stone block at (3, 1, 12)
+    fn hand_built_world() -> World {
+        let mut world = World::test_floor("stone");
+        let registry = world.registry();
+        let (stone, slab) = (
+            registry.by_name("stone").unwrap(),
+            registry.by_name("stone_slab").unwrap(),
+        );
+        for y in 1..4 {
+            for z in 0..16 {
+                world.set_block(cgmath::vec3(8, y, z), stone);
+            }
+        }
+        world.set_block(cgmath::vec3(3, 1, 10), slab);
+        world.set_block(cgmath::vec3(3, 1, 12), stone);
+        world
+    }
+
+    fn run(
+        player: &mut Player,
+        world: &World,
+        walk: cgmath::Vector2<f32>,
+        jump: bool,
+        seconds: f32,
+    ) {
+        let dt = 1.0 / 60.0;
+        for _ in 0..(seconds / dt) as u32 {
+            player.step(world, walk, jump, dt);
+        }
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{actual} instead of {expected}"
+        );
+    }
+
+    #[test]
+    fn players_fall_and_jump() {
+        let world = hand_built_world();
+        let mut player = Player::new(cgmath::vec3(4.0, 6.0, 4.0));
+        run(&mut player, &world, cgmath::vec2(0.0, 0.0), false, 2.0);
+        assert!(player.on_ground);
+        assert_near(player.position.y, 0.5);
+        assert_eq!(player.velocity.y, 0.0);
+
+        player.step(&world, cgmath::vec2(0.0, 0.0), true, 1.0 / 60.0);
+        assert!(!player.on_ground);
+        run(&mut player, &world, cgmath::vec2(0.0, 0.0), false, 0.25);
+        // high enough to get onto a block
+        assert!(player.position.y > 1.5);
+        run(&mut player, &world, cgmath::vec2(0.0, 0.0), false, 1.0);
+        assert!(player.on_ground);
+        assert_near(player.position.y, 0.5);
+    }
+
+    #[test]
+    fn players_slide_along_walls() {
+        let world = hand_built_world();
+        let mut player = Player::new(cgmath::vec3(6.0, 0.5, 5.0));
+        run(&mut player, &world, cgmath::vec2(4.0, 4.0), false, 0.5);
+        assert_near(player.position.x, 7.5 - PLAYER_WIDTH / 2.0);
+        assert!(player.position.z > 6.9);
+        assert_near(player.position.y, 0.5);
+    }
+
+    #[test]
+    fn players_step_onto_slabs_but_not_blocks() {
+        let world = hand_built_world();
+        let mut player = Player::new(cgmath::vec3(1.0, 0.5, 10.0));
+        run(&mut player, &world, cgmath::vec2(0.0, 0.0), false, 0.1);
+        run(&mut player, &world, cgmath::vec2(4.0, 0.0), false, 0.5);
+        assert_near(player.position.y, 1.0);
+        assert!(player.position.x > 2.5);
+
+        let mut player = Player::new(cgmath::vec3(1.0, 0.5, 12.0));
+        run(&mut player, &world, cgmath::vec2(0.0, 0.0), false, 0.1);
+        run(&mut player, &world, cgmath::vec2(4.0, 0.0), false, 0.5);
+        assert_near(player.position.y, 0.5);
+        assert_near(player.position.x, 2.5 - PLAYER_WIDTH / 2.0);
+    }
+}
@@ -0,0 +1,282 @@
+use cgmath::InnerSpace;
+
+use crate::{
+    chunk::Block,
+    math::{Motor, Point},
+    model,
+    state::Direction,
+    world::World,
+};
+
+/// A half line that starts at `origin`, in the same space as block positions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: cgmath::Vector3<f32>,
+    /// Always has a length of 1, so distances along the ray are in blocks
+    pub direction: cgmath::Vector3<f32>,
+}
+
+impl Ray {
+    /// Returns `None` if the direction is zero
+    pub fn new(origin: cgmath::Vector3<f32>, direction: cgmath::Vector3<f32>) -> Option<Self> {
+        (direction.magnitude2() > 0.0).then(|| Self {
+            origin,
+            direction: direction.normalize(),
+        })
+    }
+
+    /// The ray that goes out of the camera at `transform` along the direction it looks in
+    pub fn from_transform(transform: Motor) -> Self {
+        Self {
+            origin: Point::IDENTITY.transform(transform).into(),
+            direction: transform.forward().normalize(),
+        }
+    }
+
+    pub fn at(self, distance: f32) -> cgmath::Vector3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    /// Where the ray enters the box and through which side, from `min` to `max` in block space.
+    /// A ray that starts inside of the box enters it right away through the side that faces back along the ray
+    fn enter_box(
+        self,
+        min: cgmath::Vector3<f32>,
+        max: cgmath::Vector3<f32>,
+    ) -> Option<(f32, Direction)> {
+        let mut enter = (f32::NEG_INFINITY, Direction::default());
+        let mut exit = f32::INFINITY;
+        for axis in 0..3 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            if direction == 0.0 {
+                if origin < min[axis] || origin > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let (near, far) = if direction > 0.0 {
+                (min[axis], max[axis])
+            } else {
+                (max[axis], min[axis])
+            };
+            let near = (near - origin) / direction;
+            if near > enter.0 {
+                // the side that faces back along the ray
+                enter = (near, Direction::ALL[axis * 2 + (direction > 0.0) as usize]);
+            }
+            exit = exit.min((far - origin) / direction);
+        }
+        if enter.0 < 0.0 {
+            enter = (0.0, Direction::nearest(-self.direction));
+        }
+        (enter.0 <= exit).then_some(enter)
+    }
+}
+
+/// A block that a ray hit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    pub position: cgmath::Vector3<i32>,
+    /// The side of the block that the ray went into, the block in front of that side
+    /// is at `position + normal.offset()`
+    pub normal: Direction,
+    /// How far along the ray the block was hit, in blocks
+    pub distance: f32,
+}
+
+/// Finds the first block that the ray hits, stepping through the blocks along the ray one at a time.
+///
+/// Air and fluids are passed through, and blocks that don't fill their whole space are only hit on the
+/// boxes of their [`Model`](crate::registry::Model). The ray stops without hitting anything after
+/// `max_distance` blocks, or at the first block whose chunk isn't loaded
+pub fn raycast(world: &World, ray: Ray, max_distance: f32) -> Option<RaycastHit> {
+    // blocks are centered on their positions, so the grid lines are halfway between them
+    let start = ray.origin + cgmath::vec3(0.5, 0.5, 0.5);
+    let mut position = start.map(|x| x.floor() as i32);
+    let step = ray.direction.map(|x| if x < 0.0 { -1 } else { 1 });
+    // how far along the ray the next grid line on each axis is, and how far apart the grid lines are
+    let mut next_boundary = cgmath::vec3(0, 1, 2).map(|axis| {
+        let direction = ray.direction[axis];
+        if direction == 0.0 {
+            f32::INFINITY
+        } else if direction > 0.0 {
+            ((position[axis] + 1) as f32 - start[axis]) / direction
+        } else {
+            (position[axis] as f32 - start[axis]) / direction
+        }
+    });
+    let boundary_spacing = ray.direction.map(|x| 1.0 / x.abs());
+
+    loop {
+        if let Some(hit) = hit_block(world, ray, position)? {
+            return (hit.distance <= max_distance).then_some(hit);
+        }
+        let axis = (0..3)
+            .reduce(|a, b| {
+                if next_boundary[b] < next_boundary[a] {
+                    b
+                } else {
+                    a
+                }
+            })
+            .unwrap();
+        if next_boundary[axis] > max_distance {
+            return None;
+        }
+        position[axis] += step[axis];
+        next_boundary[axis] += boundary_spacing[axis];
+    }
+}
+
+/// Returns `None` if the block isn't loaded, and `Some(None)` if the ray misses it
+fn hit_block(
+    world: &World,
+    ray: Ray,
+    position: cgmath::Vector3<i32>,
+) -> Option<Option<RaycastHit>> {
+    let block = world.get_block(position)?;
+    let definition = world.registry().get(block);
+    if block == Block::AIR || definition.fluid.is_some() {
+        return Some(None);
+    }
+    let boxes = model::selection_boxes(definition.model, world.block_state(position)?);
+    Some(
+        boxes
+            .into_iter()
+            .filter_map(|model_box| {
+                let (min, max) = model_box.at(position);
+                ray.enter_box(min, max)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(distance, normal)| RaycastHit {
+                position,
+                normal,
+                distance,
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A world with a stone floor, a stone wall and the given blocks
+    fn hand_built_world(blocks: &[(&str, cgmath::Vector3<i32>)]) -> World {
+        let mut world = World::test_floor("stone");
+        let stone = world.registry().by_name("stone").unwrap();
+        for y in 1..4 {
+            world.set_block(cgmath::vec3(10, y, 8), stone);
+        }
+        for &(name, position) in blocks {
+            let block = world.registry().by_name(name).unwrap();
+            world.set_block(position, block);
+        }
+        world
+    }
+
+    fn assert_hit(
+        hit: Option<RaycastHit>,
+        position: cgmath::Vector3<i32>,
+        normal: Direction,
+        distance: f32,
+    ) {
+        let hit = hit.expect("the ray should hit a block");
+        assert_eq!((hit.position, hit.normal), (position, normal));
+        assert!(
+            (hit.distance - distance).abs() < 1e-4,
+            "hit at {} instead of {distance}",
+            hit.distance
+        );
+    }
+
+    #[test]
+    fn rays_hit_the_first_block_in_range() {
+        let world = hand_built_world(&[("water", cgmath::vec3(6, 2, 8))]);
+        let down = Ray::new(cgmath::vec3(2.0, 5.0, 2.0), cgmath::vec3(0.0, -1.0, 0.0)).unwrap();
+        assert_hit(
+            raycast(&world, down, 10.0),
+            cgmath::vec3(2, 0, 2),
+            Direction::PositiveY,
+            4.5,
+        );
+        assert_eq!(raycast(&world, down, 4.0), None);
+
+        // through the water and into the wall
+        let along_x = Ray::new(cgmath::vec3(3.0, 2.0, 8.0), cgmath::vec3(1.0, 0.0, 0.0)).unwrap();
+        assert_hit(
+            raycast(&world, along_x, 10.0),
+            cgmath::vec3(10, 2, 8),
+            Direction::NegativeX,
+            6.5,
+        );
+
+        // crossing grid lines on every axis before hitting the top of the wall
+        let diagonal = Ray::new(cgmath::vec3(6.0, 6.0, 4.0), cgmath::vec3(4.0, -2.5, 4.0)).unwrap();
+        let hit = raycast(&world, diagonal, 20.0).unwrap();
+        assert_eq!(
+            (hit.position, hit.normal),
+            (cgmath::vec3(10, 3, 8), Direction::PositiveY)
+        );
+        assert!((diagonal.at(hit.distance).y - 3.5).abs() < 1e-4);
+
+        // the chunk next to the loaded one
+        let outwards = Ray::new(cgmath::vec3(3.0, 2.0, 8.0), cgmath::vec3(0.0, 0.0, 1.0)).unwrap();
+        assert_eq!(raycast(&world, outwards, 100.0), None);
+    }
+
+    #[test]
+    fn rays_only_hit_the_model_boxes() {
+        let world = hand_built_world(&[
+            ("stone_slab", cgmath::vec3(5, 1, 8)),
+            ("wheat_3", cgmath::vec3(5, 1, 4)),
+        ]);
+        let over_slab = Ray::new(cgmath::vec3(2.0, 1.2, 8.0), cgmath::vec3(1.0, 0.0, 0.0)).unwrap();
+        assert_hit(
+            raycast(&world, over_slab, 10.0),
+            cgmath::vec3(10, 1, 8),
+            Direction::NegativeX,
+            7.5,
+        );
+        let into_slab = Ray::new(cgmath::vec3(2.0, 0.8, 8.0), cgmath::vec3(1.0, 0.0, 0.0)).unwrap();
+        assert_hit(
+            raycast(&world, into_slab, 10.0),
+            cgmath::vec3(5, 1, 8),
+            Direction::NegativeX,
+            2.5,
+        );
+        let onto_slab =
+            Ray::new(cgmath::vec3(5.2, 3.0, 8.1), cgmath::vec3(0.0, -1.0, 0.0)).unwrap();
+        assert_hit(
+            raycast(&world, onto_slab, 10.0),
+            cgmath::vec3(5, 1, 8),
+            Direction::PositiveY,
+            2.0,
+        );
+
+        // plants can be hit anywhere in their space
+        let into_plant =
+            Ray::new(cgmath::vec3(5.0, 1.4, 1.0), cgmath::vec3(0.0, 0.0, 1.0)).unwrap();
+        assert_hit(
+            raycast(&world, into_plant, 10.0),
+            cgmath::vec3(5, 1, 4),
+            Direction::NegativeZ,
+            2.5,
+        );
+    }
+
+    #[test]
+    fn cameras_cast_rays_where_they_look() {
+        let world = hand_built_world(&[]);
+        // turned a quarter to the right, so the camera looks along +z at the wall
+        let camera = Motor::rotation_xz(std::f32::consts::FRAC_PI_2)
+            .apply(Motor::translation(cgmath::vec3(10.0, 2.0, 3.0)));
+        let ray = Ray::from_transform(camera);
+        assert!((ray.direction - cgmath::vec3(0.0, 0.0, 1.0)).magnitude() < 1e-4);
+        assert_hit(
+            raycast(&world, ray, 10.0),
+            cgmath::vec3(10, 2, 8),
+            Direction::NegativeZ,
+            4.5,
+        );
+    }
+}
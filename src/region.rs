@@ -0,0 +1,274 @@
+use crate::{
+    biome::Biome,
+    chunk::{Block, Chunk},
+    palette::{PaletteStorage, LEN},
+    registry::BlockRegistry,
+    state::BlockState,
+};
+use anyhow::{bail, Context};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// The number of chunks along each axis of a region
+pub const REGION_SIZE: i32 = 8;
+const CHUNKS_PER_REGION: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+const REGION_MAGIC: [u8; 4] = *b"BGRG";
+/// Bump this whenever the layout of region files or chunk payloads changes
+pub const REGION_FORMAT_VERSION: u32 = 4;
+/// magic + version + an `(offset, length)` pair for every chunk
+const HEADER_SIZE: usize = 4 + 4 + CHUNKS_PER_REGION * 8;
+
+/// Splits a chunk position into the position of the region that contains it and
+/// the index of the chunk inside of that region
+pub fn split_chunk_position(chunk_position: cgmath::Vector3<i32>) -> (cgmath::Vector3<i32>, usize) {
+    let region_position = chunk_position.map(|x| x.div_euclid(REGION_SIZE));
+    let local = chunk_position.map(|x| x.rem_euclid(REGION_SIZE) as usize);
+    let size = REGION_SIZE as usize;
+    (region_position, (local.x * size + local.y) * size + local.z)
+}
+
+pub fn region_path(directory: &Path, region_position: cgmath::Vector3<i32>) -> PathBuf {
+    directory.join(format!(
+        "r.{}.{}.{}.region",
+        region_position.x, region_position.y, region_position.z
+    ))
+}
+
+/// A group of chunks that are stored in a single file, each chunk is compressed separately
+/// so chunks can be replaced without re-encoding the whole region
+pub struct Region {
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl Region {
+    pub fn new() -> Self {
+        Self {
+            chunks: vec![None; CHUNKS_PER_REGION],
+        }
+    }
+
+    /// Returns an empty region if the file doesn't exist
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(error) => return Err(error).with_context(|| format!("reading {}", path.display())),
+        };
+        Self::from_bytes(&bytes).with_context(|| format!("reading {}", path.display()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < HEADER_SIZE || bytes[0..4] != REGION_MAGIC {
+            bail!("not a region file");
+        }
+        let mut reader = ByteReader { bytes: &bytes[4..] };
+        let version = reader.u32()?;
+        if version != REGION_FORMAT_VERSION {
+            bail!("unsupported region format version {version}, expected {REGION_FORMAT_VERSION}");
+        }
+
+        let mut chunks = Vec::with_capacity(CHUNKS_PER_REGION);
+        for _ in 0..CHUNKS_PER_REGION {
+            let offset = reader.u32()? as usize;
+            let length = reader.u32()? as usize;
+            chunks.push(if length == 0 {
+                None
+            } else {
+                let Some(payload) = bytes.get(offset..offset + length) else {
+                    bail!("chunk payload is out of bounds");
+                };
+                Some(payload.to_vec())
+            });
+        }
+        Ok(Self { chunks })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(&REGION_MAGIC);
+        bytes.extend_from_slice(&REGION_FORMAT_VERSION.to_le_bytes());
+
+        let mut offset = HEADER_SIZE;
+        for payload in &self.chunks {
+            let length = payload.as_ref().map_or(0, Vec::len);
+            let start = if length == 0 { 0 } else { offset };
+            bytes.extend_from_slice(&(start as u32).to_le_bytes());
+            bytes.extend_from_slice(&(length as u32).to_le_bytes());
+            offset += length;
+        }
+        for payload in self.chunks.iter().flatten() {
+            bytes.extend_from_slice(payload);
+        }
+        bytes
+    }
+
+    /// Writes to a temporary file first so a crash while saving can't corrupt the region
+    pub fn write(&self, path: &Path) -> anyhow::Result<()> {
+        let temporary_path = path.with_extension("region.tmp");
+        std::fs::write(&temporary_path, self.to_bytes())
+            .with_context(|| format!("writing {}", temporary_path.display()))?;
+        std::fs::rename(&temporary_path, path)
+            .with_context(|| format!("writing {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Fails if the chunk contains blocks that are not in the registry
+    pub fn get_chunk(
+        &self,
+        index: usize,
+        registry: &BlockRegistry,
+    ) -> anyhow::Result<Option<Chunk>> {
+        let Some(payload) = &self.chunks[index] else {
+            return Ok(None);
+        };
+        let mut bytes = vec![];
+        ZlibDecoder::new(payload.as_slice()).read_to_end(&mut bytes)?;
+        decode_chunk(&bytes, registry).map(Some)
+    }
+
+    pub fn set_chunk(&mut self, index: usize, chunk: &Chunk) -> anyhow::Result<()> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&encode_chunk(chunk))?;
+        self.chunks[index] = Some(encoder.finish()?);
+        Ok(())
+    }
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) struct ByteReader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn take(&mut self, length: usize) -> anyhow::Result<&'a [u8]> {
+        if self.bytes.len() < length {
+            bail!("unexpected end of data");
+        }
+        let (taken, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub(crate) fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub(crate) fn f32(&mut self) -> anyhow::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+/// The uncompressed chunk payload: biomes, then the blocks, then the fluid levels, then the block states
+/// packed with [`BlockState::pack`]
+pub fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    let mut bytes = vec![];
+    for biome in chunk.biomes.iter().flatten() {
+        bytes.push(biome.id());
+    }
+    encode_palette_storage(&mut bytes, &chunk.blocks, |bytes, block| {
+        bytes.extend_from_slice(&block.0.to_le_bytes())
+    });
+    encode_palette_storage(&mut bytes, &chunk.fluid_levels, |bytes, level| {
+        bytes.push(level)
+    });
+    encode_palette_storage(&mut bytes, &chunk.states, |bytes, state| {
+        bytes.push(state.pack())
+    });
+    bytes
+}
+
+/// The palette, then the number of bits per index, then the packed indices
+fn encode_palette_storage<T: Copy + Eq>(
+    bytes: &mut Vec<u8>,
+    storage: &PaletteStorage<T>,
+    mut encode_value: impl FnMut(&mut Vec<u8>, T),
+) {
+    let palette = storage.palette();
+    bytes.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+    for &value in palette {
+        encode_value(bytes, value);
+    }
+    bytes.push(storage.bits_per_entry() as u8);
+    if let PaletteStorage::Paletted { data, .. } = storage {
+        for word in data.iter() {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+    }
+}
+
+pub fn decode_chunk(bytes: &[u8], registry: &BlockRegistry) -> anyhow::Result<Chunk> {
+    let mut reader = ByteReader { bytes };
+    let mut chunk = Chunk::new();
+    for biome in chunk.biomes.iter_mut().flatten() {
+        let id = reader.u8()?;
+        *biome = Biome::from_id(id).with_context(|| format!("unknown biome id {id}"))?;
+    }
+
+    chunk.blocks = decode_palette_storage(&mut reader, |reader| {
+        let block = Block(reader.u16()?);
+        if !registry.contains(block) {
+            bail!("unknown block id {}", block.0);
+        }
+        Ok(block)
+    })
+    .context("invalid block palette")?;
+    chunk.fluid_levels = decode_palette_storage(&mut reader, ByteReader::u8)
+        .context("invalid fluid level palette")?;
+    chunk.states = decode_palette_storage(&mut reader, |reader| {
+        let packed = reader.u8()?;
+        BlockState::unpack(packed).with_context(|| format!("invalid block state {packed:#x}"))
+    })
+    .context("invalid block state palette")?;
+
+    if !reader.bytes.is_empty() {
+        bail!("trailing data after chunk");
+    }
+    Ok(chunk)
+}
+
+fn decode_palette_storage<'a, T: Copy + Eq>(
+    reader: &mut ByteReader<'a>,
+    mut decode_value: impl FnMut(&mut ByteReader<'a>) -> anyhow::Result<T>,
+) -> anyhow::Result<PaletteStorage<T>> {
+    let palette_len = reader.u16()? as usize;
+    let palette = (0..palette_len)
+        .map(|_| decode_value(reader))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let bits = reader.u8()? as u32;
+    if bits == 0 {
+        let [value] = palette[..] else {
+            bail!("single value storage must have exactly one palette entry");
+        };
+        Ok(PaletteStorage::Single(value))
+    } else {
+        let data = (0..LEN * bits as usize / 64)
+            .map(|_| reader.u64())
+            .collect::<anyhow::Result<Box<[u64]>>>()?;
+        PaletteStorage::from_raw_parts(palette, bits, data).context("palette index out of range")
+    }
+}
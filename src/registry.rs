@@ -0,0 +1,402 @@
+use crate::{
+    biome::Biome,
+    chunk::Block,
+    light::MAX_LIGHT,
+    state::{BlockState, Direction},
+};
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+
+/// How the color of a block is changed by the biome it is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Tint {
+    #[default]
+    None,
+    /// Multiplied by [`Biome::grass_color`]
+    Grass,
+    /// Multiplied by [`Biome::foliage_color`]
+    Foliage,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_alpha() -> f32 {
+    1.0
+}
+
+/// Which way a block can be turned, this decides the [`BlockState::facing`] of blocks when they are placed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Orientation {
+    #[default]
+    None,
+    /// Lined up with one of the three axes, like a log
+    Axis,
+    /// Faces any of the six directions, like a dispenser
+    Facing,
+    /// Faces one of the four horizontal directions, like a furnace
+    HorizontalFacing,
+}
+
+/// The shape of a block, see the [`model`](crate::model) module
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum Model {
+    #[default]
+    Cube,
+    /// Half of a block, in the bottom or top half depending on the state
+    Slab,
+    /// A slab with a step on the back half of it, away from the side it faces
+    Stairs,
+    /// A thin plate in the bottom or top half, that stands up against its back side when it is open
+    Trapdoor,
+    /// A thin plate through the middle of the block, across the direction it faces
+    Pane,
+    /// Two planes that cross diagonally through the block, for plants
+    Cross,
+}
+
+/// How a fluid block flows, see the [`fluid`](crate::fluid) module
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FluidDefinition {
+    /// How many levels the fluid loses for every block it flows sideways
+    pub level_drop: u8,
+    /// How many ticks it takes the fluid to flow into the next block
+    pub tick_delay: u32,
+    /// The name of the block that the fluid turns into when it touches a different fluid
+    #[serde(default)]
+    pub hardens_into: Option<String>,
+}
+
+/// How a block changes over time, see the [`behavior`](crate::behavior) module.
+/// Blocks are referred to by their names
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+pub enum Behavior {
+    #[default]
+    None,
+    /// Falls down through air and fluids when a block next to it changes
+    Falling,
+    /// Spreads onto nearby `onto` blocks that have light above them,
+    /// and turns into `covered` when the block above it isn't transparent
+    Spreading { onto: String, covered: String },
+    /// Grows into the next stage when the light level of the block is at least `min_light`
+    Growing { into: String, min_light: u8 },
+    /// Turns into air when there isn't a `sustained_by` block within `distance` blocks,
+    /// counted through blocks of the same kind
+    Decaying { sustained_by: String, distance: u8 },
+}
+
+impl Behavior {
+    /// Whether the behavior runs on random ticks
+    pub fn ticks_randomly(&self) -> bool {
+        match self {
+            Behavior::None | Behavior::Falling => false,
+            Behavior::Spreading { .. } | Behavior::Growing { .. } | Behavior::Decaying { .. } => {
+                true
+            }
+        }
+    }
+
+    fn referenced_blocks(&self) -> Vec<&str> {
+        match self {
+            Behavior::None | Behavior::Falling => vec![],
+            Behavior::Spreading { onto, covered } => vec![onto, covered],
+            Behavior::Growing { into, .. } => vec![into],
+            Behavior::Decaying { sustained_by, .. } => vec![sustained_by],
+        }
+    }
+}
+
+/// A kind of block, as it is written in the block definition file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockDefinition {
+    /// The id that chunks store, this must never change once worlds have been saved with it
+    pub id: u16,
+    pub name: String,
+    pub color: [f32; 3],
+    /// How opaque the block is, blocks with an alpha below 1 are blended over what is behind them
+    #[serde(default = "default_alpha")]
+    pub alpha: f32,
+    #[serde(default)]
+    pub tint: Tint,
+    /// The color of the front faces of the block, see [`BlockState::is_front`]. Defaults to `color`
+    #[serde(default)]
+    pub front_color: Option<[f32; 3]>,
+    #[serde(default)]
+    pub model: Model,
+    #[serde(default)]
+    pub orientation: Orientation,
+    /// Whether the block can be placed in the top or bottom half of a block, like a slab
+    #[serde(default)]
+    pub has_halves: bool,
+    /// Whether the block can be opened and closed, like a door
+    #[serde(default)]
+    pub openable: bool,
+    /// Whether the block blocks movement
+    #[serde(default = "default_true")]
+    pub solid: bool,
+    /// Whether the faces of blocks behind this one can be seen through it,
+    /// translucent blocks must be transparent
+    #[serde(default)]
+    pub transparent: bool,
+    /// The light level the block emits, in the range `0..=MAX_LIGHT`
+    #[serde(default)]
+    pub light_emission: u8,
+    /// When structures overlap each other or the terrain the block with the higher priority wins,
+    /// ties are broken by the id so the order is total
+    #[serde(default)]
+    pub placement_priority: u8,
+    /// Fluid blocks flow into the air around them, they should not be solid
+    #[serde(default)]
+    pub fluid: Option<FluidDefinition>,
+    #[serde(default)]
+    pub behavior: Behavior,
+}
+
+impl BlockDefinition {
+    /// Whether the block has to be rendered with blending
+    pub fn is_translucent(&self) -> bool {
+        self.alpha < 1.0
+    }
+
+    /// The color of the face of a block that points in `direction`, faces are colored as if the block was turned
+    /// the way its state says
+    pub fn face_color(
+        &self,
+        biome: Biome,
+        state: BlockState,
+        direction: Direction,
+    ) -> cgmath::Vector3<f32> {
+        let color = match self.front_color {
+            Some(front_color) if state.is_front(self.orientation, direction) => front_color,
+            _ => self.color,
+        };
+        self.tinted(color.into(), biome)
+    }
+
+    pub fn color(&self, biome: Biome) -> cgmath::Vector3<f32> {
+        self.tinted(self.color.into(), biome)
+    }
+
+    fn tinted(&self, color: cgmath::Vector3<f32>, biome: Biome) -> cgmath::Vector3<f32> {
+        let tint = match self.tint {
+            Tint::None => return color,
+            Tint::Grass => biome.grass_color(),
+            Tint::Foliage => biome.foliage_color(),
+        };
+        cgmath::vec3(color.x * tint.x, color.y * tint.y, color.z * tint.z)
+    }
+}
+
+/// All the kinds of blocks, loaded from a definition file so blocks can be added without changing the code.
+///
+/// The block with id 0 is always air, which is what empty chunks are filled with
+pub struct BlockRegistry {
+    /// Indexed by the block id, ids don't have to be contiguous
+    definitions: Vec<Option<BlockDefinition>>,
+    ids_by_name: HashMap<String, Block>,
+}
+
+impl BlockRegistry {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Self::from_ron_str(&source).with_context(|| format!("loading {}", path.display()))
+    }
+
+    /// Parses a list of [`BlockDefinition`]s in the RON format
+    pub fn from_ron_str(source: &str) -> anyhow::Result<Self> {
+        Self::new(ron::from_str(source)?)
+    }
+
+    pub fn new(definitions: Vec<BlockDefinition>) -> anyhow::Result<Self> {
+        let mut registry = Self {
+            definitions: vec![],
+            ids_by_name: HashMap::new(),
+        };
+        for definition in definitions {
+            let block = Block(definition.id);
+            if definition.light_emission > MAX_LIGHT {
+                bail!(
+                    "block {:?} emits more than the maximum light level of {MAX_LIGHT}",
+                    definition.name
+                );
+            }
+            if !(0.0..=1.0).contains(&definition.alpha) {
+                bail!("block {:?} has an alpha outside of 0..=1", definition.name);
+            }
+            if definition.is_translucent() && !definition.transparent {
+                bail!(
+                    "block {:?} is translucent so it must also be transparent",
+                    definition.name
+                );
+            }
+            if definition.front_color.is_some() && definition.orientation == Orientation::None {
+                bail!(
+                    "block {:?} has a front color but no orientation",
+                    definition.name
+                );
+            }
+            if let Some(fluid) = &definition.fluid {
+                if fluid.level_drop == 0 || fluid.tick_delay == 0 {
+                    bail!(
+                        "fluid {:?} must have a level drop and tick delay of at least 1",
+                        definition.name
+                    );
+                }
+                if definition.model != Model::Cube {
+                    bail!("fluid {:?} must have a cube model", definition.name);
+                }
+            }
+            if registry.contains(block) {
+                bail!("block id {} is used more than once", definition.id);
+            }
+            if registry
+                .ids_by_name
+                .insert(definition.name.clone(), block)
+                .is_some()
+            {
+                bail!("block name {:?} is used more than once", definition.name);
+            }
+
+            let index = definition.id as usize;
+            if registry.definitions.len() <= index {
+                registry.definitions.resize(index + 1, None);
+            }
+            registry.definitions[index] = Some(definition);
+        }
+
+        match registry.definitions.first() {
+            Some(Some(air)) if air.name == "air" && !air.solid && air.transparent => {}
+            _ => bail!("block id 0 must be a transparent non solid block named \"air\""),
+        }
+        for definition in registry.definitions.iter().flatten() {
+            if let Some(hardens_into) = definition
+                .fluid
+                .as_ref()
+                .and_then(|fluid| fluid.hardens_into.as_ref())
+            {
+                registry.by_name(hardens_into).with_context(|| {
+                    format!("fluid {:?} hardens into an unknown block", definition.name)
+                })?;
+            }
+            for name in definition.behavior.referenced_blocks() {
+                registry.by_name(name).with_context(|| {
+                    format!("the behavior of block {:?} is invalid", definition.name)
+                })?;
+            }
+            if let Behavior::Growing { min_light, .. } = definition.behavior {
+                if min_light > MAX_LIGHT {
+                    bail!(
+                        "block {:?} needs more than the maximum light level of {MAX_LIGHT} to grow",
+                        definition.name
+                    );
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn contains(&self, block: Block) -> bool {
+        matches!(self.definitions.get(block.0 as usize), Some(Some(_)))
+    }
+
+    /// Panics if the block is not in the registry, all the blocks in chunks have been checked with [`BlockRegistry::contains`]
+    pub fn get(&self, block: Block) -> &BlockDefinition {
+        self.definitions
+            .get(block.0 as usize)
+            .and_then(Option::as_ref)
+            .unwrap_or_else(|| panic!("block id {} is not registered", block.0))
+    }
+
+    pub fn by_name(&self, name: &str) -> anyhow::Result<Block> {
+        self.ids_by_name
+            .get(name)
+            .copied()
+            .with_context(|| format!("there is no block named {name:?}"))
+    }
+
+    pub fn blocks(&self) -> impl Iterator<Item = (Block, &BlockDefinition)> {
+        self.definitions
+            .iter()
+            .filter_map(|definition| definition.as_ref())
+            .map(|definition| (Block(definition.id), definition))
+    }
+
+    /// The key that decides which block wins when structures overlap, see [`BlockDefinition::placement_priority`]
+    pub fn placement_priority(&self, block: Block) -> (u8, u16) {
+        (self.get(block).placement_priority, block.0)
+    }
+
+    /// Returns `None` if the block isn't a fluid
+    pub fn fluid(&self, block: Block) -> Option<&FluidDefinition> {
+        self.get(block).fluid.as_ref()
+    }
+
+    /// The block that a fluid turns into when it touches a different fluid, if it hardens at all
+    pub fn hardens_into(&self, block: Block) -> Option<Block> {
+        let name = self.fluid(block)?.hardens_into.as_ref()?;
+        Some(
+            self.by_name(name)
+                .expect("the registry checks that the block exists"),
+        )
+    }
+}
+
+/// The registry that the game ships with, for tests that need real blocks
+#[cfg(test)]
+pub(crate) fn default_registry() -> BlockRegistry {
+    BlockRegistry::load(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/blocks.ron")).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_registry_loads() {
+        let registry = default_registry();
+        assert_eq!(registry.by_name("air").unwrap(), Block::AIR);
+        assert!(registry.blocks().count() > 1);
+    }
+
+    #[test]
+    fn invalid_definitions_are_rejected() {
+        let air =
+            r#"(id: 0, name: "air", color: (0.0, 0.0, 0.0), solid: false, transparent: true)"#;
+        for source in [
+            // no air
+            r#"[(id: 0, name: "stone", color: (0.5, 0.5, 0.5))]"#.to_string(),
+            format!(r#"[{air}, (id: 0, name: "stone", color: (0.5, 0.5, 0.5))]"#),
+            format!(r#"[{air}, (id: 1, name: "air", color: (0.5, 0.5, 0.5))]"#),
+            format!(
+                r#"[{air}, (id: 1, name: "lamp", color: (1.0, 1.0, 1.0), light_emission: 16)]"#
+            ),
+            format!(r#"[{air}, (id: 1, name: "stone", colour: (0.5, 0.5, 0.5))]"#),
+            format!(r#"[{air}, (id: 1, name: "glass", color: (1.0, 1.0, 1.0), alpha: 0.5)]"#),
+            format!(
+                r#"[{air}, (id: 1, name: "furnace", color: (0.4, 0.4, 0.4), front_color: Some((0.9, 0.5, 0.2)))]"#
+            ),
+            format!(
+                r#"[{air}, (id: 1, name: "sapling", color: (0.2, 0.6, 0.1), behavior: Growing(into: "tree", min_light: 8))]"#
+            ),
+            format!(
+                r#"[{air}, (id: 1, name: "lava", color: (1.0, 0.5, 0.0), fluid: Some((level_drop: 2, tick_delay: 10, hardens_into: Some("stone"))))]"#
+            ),
+        ] {
+            assert!(BlockRegistry::from_ron_str(&source).is_err(), "{source}");
+        }
+
+        let registry = BlockRegistry::from_ron_str(&format!(
+            r#"[{air}, (id: 7, name: "stone", color: (0.5, 0.5, 0.5))]"#
+        ))
+        .unwrap();
+        assert_eq!(registry.by_name("stone").unwrap(), Block(7));
+        assert!(!registry.contains(Block(3)));
+        assert!(registry.get(Block(7)).solid);
+    }
+}
@@ -5,6 +5,7 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) face_index: u32,
+    @location(1) ambient_occlusion: f32,
 };
 
 struct Point {
@@ -86,14 +87,37 @@ struct Camera {
 @binding(0)
 var<uniform> camera: Camera;
 
+// moves a position from world space into the space of the camera, where the camera looks along +x
+fn to_camera_space(position: vec3<f32>) -> vec3<f32> {
+    let inverse_camera_transform = inverse_motor(camera.transform);
+    return point_to_vec3(transform_point(vec3_to_point(position), inverse_camera_transform));
+}
+
+fn project(position: vec3<f32>) -> vec4<f32> {
+    return vec4<f32>(
+        position.z / camera.aspect,
+        position.y,
+        -position.x * -(camera.far_clip + camera.near_clip) / (camera.far_clip - camera.near_clip) - (2.0 * camera.far_clip * camera.near_clip) / (camera.far_clip - camera.near_clip),
+        position.x,
+    );
+}
+
 struct Face {
     position: vec3<f32>,
+    size: vec3<f32>,
     normal: vec3<f32>,
-    color: vec3<f32>,
+    color: vec4<f32>,
+    // sky light and block light
+    light: vec2<f32>,
+    ambient_occlusion: vec4<f32>,
+    direction: u32,
 }
 
 struct Faces {
-    vertices: array<vec3<f32>, 6>,
+    // the corners of a unit quad for each face direction, the six sides of a cube and both sides of the two planes of a cross
+    corners: array<array<vec3<f32>, 4>, 10>,
+    // the corners of the two triangles of a quad for each face direction, split along either diagonal
+    triangles: array<array<array<u32, 6>, 2>, 10>,
     faces: array<Face>,
 }
 
@@ -106,15 +130,17 @@ fn vertex(input: VertexInput) -> VertexOutput {
     var output: VertexOutput;
     output.face_index = input.vertex_index / 6u;
 
-    let inverse_camera_transform = inverse_motor(camera.transform);
-    let position = point_to_vec3(transform_point(vec3_to_point(faces.vertices[input.vertex_index % 6u] + faces.faces[output.face_index].position), inverse_camera_transform));
+    let face = faces.faces[output.face_index];
+    // split the quad along the diagonal with the brighter corners, so the occlusion doesn't stretch along the diagonal
+    let ambient_occlusion = face.ambient_occlusion;
+    let flipped = u32(ambient_occlusion.y + ambient_occlusion.z > ambient_occlusion.x + ambient_occlusion.w);
+    let corner = faces.triangles[face.direction][flipped][input.vertex_index % 6u];
+    output.ambient_occlusion = ambient_occlusion[corner];
+    // the corners are for a unit quad centered on the block, stretch it to cover `size` blocks
+    let unit_corner = faces.corners[face.direction][corner];
+    let vertex = (unit_corner + 0.5) * face.size - 0.5;
 
-    output.clip_position = vec4<f32>(
-        position.z / camera.aspect,
-        position.y,
-        -position.x * -(camera.far_clip + camera.near_clip) / (camera.far_clip - camera.near_clip) - (2.0 * camera.far_clip * camera.near_clip) / (camera.far_clip - camera.near_clip),
-        position.x,
-    );
+    output.clip_position = project(to_camera_space(vertex + face.position));
 
     return output;
 }
@@ -122,6 +148,26 @@ fn vertex(input: VertexInput) -> VertexOutput {
 @fragment
 fn pixel(input: VertexOutput) -> @location(0) vec4<f32> {
     let light_dir = vec3<f32>(0.3, -0.6, 0.2);
-    let light = dot(light_dir, -faces.faces[input.face_index].normal) * 0.5 + 0.5;
-    return vec4<f32>(faces.faces[input.face_index].color * light, 1.0);
+    let face = faces.faces[input.face_index];
+    let shading = dot(light_dir, -face.normal) * 0.5 + 0.5;
+    // every light level is a bit darker than the one above it, so enclosed spaces are almost black
+    let level = max(face.light.x, face.light.y);
+    let brightness = max(pow(0.8, (1.0 - level) * 15.0), 0.03);
+    let occlusion = mix(0.4, 1.0, input.ambient_occlusion);
+    return vec4<f32>(face.color.rgb * shading * brightness * occlusion, face.color.a);
+}
+
+// how much closer to the camera outlines are drawn, as a fraction of their distance,
+// so the faces that they lie on don't hide them
+const OUTLINE_DEPTH_BIAS: f32 = 0.002;
+
+@vertex
+fn outline_vertex(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    // moving along the line of sight doesn't move the vertex on screen, only its depth changes
+    return project(to_camera_space(position) * (1.0 - OUTLINE_DEPTH_BIAS));
+}
+
+@fragment
+fn outline_pixel() -> @location(0) vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 0.6);
 }
@@ -0,0 +1,211 @@
+use crate::{
+    registry::{BlockDefinition, Orientation},
+    world::DIRECTIONS,
+};
+
+/// One of the six directions that a block can face, in the same order as [`DIRECTIONS`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Direction {
+    PositiveX,
+    NegativeX,
+    #[default]
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::PositiveX,
+        Direction::NegativeX,
+        Direction::PositiveY,
+        Direction::NegativeY,
+        Direction::PositiveZ,
+        Direction::NegativeZ,
+    ];
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    /// Returns `None` if the offset isn't one of the [`DIRECTIONS`]
+    pub fn from_offset(offset: cgmath::Vector3<i32>) -> Option<Self> {
+        let index = DIRECTIONS
+            .iter()
+            .position(|&direction| direction == offset)?;
+        Some(Self::ALL[index])
+    }
+
+    /// The direction that is closest to `vector`, ties go to the x axis first and then the y axis
+    pub fn nearest(vector: cgmath::Vector3<f32>) -> Self {
+        let axis = (0..3)
+            .reduce(|a, b| {
+                if vector[b].abs() > vector[a].abs() {
+                    b
+                } else {
+                    a
+                }
+            })
+            .unwrap();
+        Self::along(axis, vector[axis] >= 0.0)
+    }
+
+    fn along(axis: usize, positive: bool) -> Self {
+        Self::ALL[axis * 2 + !positive as usize]
+    }
+
+    pub fn offset(self) -> cgmath::Vector3<i32> {
+        DIRECTIONS[self as usize]
+    }
+
+    /// The index of the axis that the direction is along, 0 for x, 1 for y and 2 for z
+    pub fn axis(self) -> usize {
+        self as usize / 2
+    }
+
+    /// Whether the direction points towards the positive end of its axis
+    pub fn is_positive(self) -> bool {
+        (self as usize).is_multiple_of(2)
+    }
+
+    pub fn opposite(self) -> Self {
+        Self::ALL[self as usize ^ 1]
+    }
+}
+
+/// Which half of the block space a block like a slab is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Half {
+    #[default]
+    Bottom,
+    Top,
+}
+
+/// The properties of a single block that can differ between blocks of the same kind, chunks store it
+/// next to the block. Which of the properties a block uses is decided by its [`BlockDefinition`],
+/// the others are left at their defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BlockState {
+    /// Which way the front of the block faces. Blocks with an [`Orientation::Axis`] only use the axis of it,
+    /// and are always turned towards the positive end
+    pub facing: Direction,
+    pub half: Half,
+    pub open: bool,
+}
+
+impl BlockState {
+    /// Packs the state into a single byte, for storing in chunks
+    pub fn pack(self) -> u8 {
+        self.facing as u8 | (self.half as u8) << 3 | (self.open as u8) << 4
+    }
+
+    /// Returns `None` if the byte wasn't made by [`BlockState::pack`]
+    pub fn unpack(packed: u8) -> Option<Self> {
+        if packed >> 5 != 0 {
+            return None;
+        }
+        Some(Self {
+            facing: Direction::from_index(packed & 0b111)?,
+            half: if packed & 1 << 3 == 0 {
+                Half::Bottom
+            } else {
+                Half::Top
+            },
+            open: packed & 1 << 4 != 0,
+        })
+    }
+
+    /// The state of a block that is placed by someone looking along `look`, like the
+    /// [`forward`](crate::math::Motor::forward) direction of the camera. `hit_height` is how far above the
+    /// center of the new block the face that it was placed against was clicked, from `-0.5` to `0.5`.
+    ///
+    /// Blocks that face a direction are turned towards whoever placed them,
+    /// and blocks with an axis are lined up with the look direction
+    pub fn placed(
+        definition: &BlockDefinition,
+        look: cgmath::Vector3<f32>,
+        hit_height: f32,
+    ) -> Self {
+        let facing = match definition.orientation {
+            Orientation::None => Direction::default(),
+            Orientation::Axis => Direction::along(Direction::nearest(look).axis(), true),
+            Orientation::Facing => Direction::nearest(-look),
+            Orientation::HorizontalFacing => {
+                Direction::nearest(cgmath::vec3(-look.x, 0.0, -look.z))
+            }
+        };
+        let half = if definition.has_halves && hit_height > 0.0 {
+            Half::Top
+        } else {
+            Half::Bottom
+        };
+        Self {
+            facing,
+            half,
+            open: false,
+        }
+    }
+
+    /// Whether the face of the block that points in `direction` is one of its front faces.
+    /// Blocks with an axis have a front face on both ends of it
+    pub fn is_front(self, orientation: Orientation, direction: Direction) -> bool {
+        match orientation {
+            Orientation::None => false,
+            Orientation::Axis => direction.axis() == self.facing.axis(),
+            Orientation::Facing | Orientation::HorizontalFacing => direction == self.facing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::Motor, registry::default_registry};
+
+    #[test]
+    fn placed_blocks_face_the_camera() {
+        let registry = default_registry();
+        let log = registry.get(registry.by_name("log").unwrap());
+        let furnace = registry.get(registry.by_name("furnace").unwrap());
+        let stone = registry.get(registry.by_name("stone").unwrap());
+
+        // turned a quarter to the right and looking a bit down, so the camera looks mostly along +z
+        let camera = Motor::rotation_xz(std::f32::consts::FRAC_PI_2)
+            .apply(Motor::rotation_xy(-0.3))
+            .apply(Motor::translation(cgmath::vec3(4.0, 20.0, -2.0)));
+        let look = camera.forward();
+        assert_eq!(Direction::nearest(look), Direction::PositiveZ);
+
+        let state = BlockState::placed(furnace, look, 0.3);
+        assert_eq!(state.facing, Direction::NegativeZ);
+        assert_eq!(state.half, Half::Bottom);
+        assert!(state.is_front(furnace.orientation, Direction::NegativeZ));
+        assert!(!state.is_front(furnace.orientation, Direction::PositiveZ));
+
+        let state = BlockState::placed(log, look, 0.0);
+        assert_eq!(state.facing, Direction::PositiveZ);
+        assert!(state.is_front(log.orientation, Direction::NegativeZ));
+        // looking straight down stands the log upright
+        let state = BlockState::placed(log, cgmath::vec3(0.1, -1.0, 0.0), 0.0);
+        assert_eq!(state.facing, Direction::PositiveY);
+
+        assert_eq!(BlockState::placed(stone, look, 0.3), BlockState::default());
+    }
+
+    #[test]
+    fn states_pack_into_a_byte() {
+        for facing in Direction::ALL {
+            for half in [Half::Bottom, Half::Top] {
+                for open in [false, true] {
+                    let state = BlockState { facing, half, open };
+                    assert_eq!(BlockState::unpack(state.pack()), Some(state));
+                }
+            }
+            assert_eq!(Direction::from_offset(facing.offset()), Some(facing));
+            assert_eq!(facing.opposite().offset(), -facing.offset());
+        }
+        assert_eq!(BlockState::unpack(0b110), None);
+        assert_eq!(BlockState::unpack(1 << 5), None);
+    }
+}
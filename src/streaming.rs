@@ -0,0 +1,65 @@
+/// How far around a center chunk, usually the one the camera is in, chunks are kept loaded.
+/// The loaded area is a cylinder so the world looks the same in every horizontal direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoadRadius {
+    /// In chunks, along the x and z axes
+    pub horizontal: i32,
+    /// In chunks, along the y axis
+    pub vertical: i32,
+}
+
+impl LoadRadius {
+    pub fn contains(
+        self,
+        center: cgmath::Vector3<i32>,
+        chunk_position: cgmath::Vector3<i32>,
+    ) -> bool {
+        let offset = chunk_position - center;
+        offset.x * offset.x + offset.z * offset.z <= self.horizontal * self.horizontal
+            && offset.y.abs() <= self.vertical
+    }
+
+    /// Every chunk position inside the radius, the ones closest to the center come first
+    pub fn chunk_positions(self, center: cgmath::Vector3<i32>) -> Vec<cgmath::Vector3<i32>> {
+        let mut chunk_positions = vec![];
+        for x in -self.horizontal..=self.horizontal {
+            for y in -self.vertical..=self.vertical {
+                for z in -self.horizontal..=self.horizontal {
+                    let chunk_position = center + cgmath::vec3(x, y, z);
+                    if self.contains(center, chunk_position) {
+                        chunk_positions.push(chunk_position);
+                    }
+                }
+            }
+        }
+        chunk_positions.sort_by_key(|position| {
+            let offset = position - center;
+            offset.x * offset.x + offset.y * offset.y + offset.z * offset.z
+        });
+        chunk_positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_are_loaded_in_a_cylinder() {
+        let radius = LoadRadius {
+            horizontal: 3,
+            vertical: 1,
+        };
+        let center = cgmath::vec3(10, -4, 2);
+        let chunk_positions = radius.chunk_positions(center);
+        assert_eq!(chunk_positions[0], center);
+        assert!(chunk_positions.contains(&cgmath::vec3(13, -5, 2)));
+        assert!(!chunk_positions.contains(&cgmath::vec3(13, -4, 3)));
+        assert!(!chunk_positions.contains(&cgmath::vec3(10, -2, 2)));
+        // a circle with a radius of 3 covers 29 chunks
+        assert_eq!(chunk_positions.len(), 29 * 3);
+        assert!(chunk_positions
+            .iter()
+            .all(|&position| radius.contains(center, position)));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{biome::Biome, chunk::Block, generation::GenerationBlocks, noise::Random};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Structure {
+    Tree { trunk_height: i32 },
+    Boulder { radius: i32 },
+    Ruin { size: i32, seed: u64 },
+}
+
+impl Structure {
+    /// Picks a structure that fits the biome
+    pub fn choose(biome: Biome, random: &mut Random) -> Self {
+        let roll = random.next_f64();
+        let tree = |random: &mut Random| Structure::Tree {
+            trunk_height: random.range(4..=6),
+        };
+        let boulder = |random: &mut Random| Structure::Boulder {
+            radius: random.range(1..=2),
+        };
+        let ruin = |random: &mut Random| Structure::Ruin {
+            size: random.range(5..=7),
+            seed: random.next_u64(),
+        };
+        match biome {
+            Biome::Forest if roll < 0.9 => tree(random),
+            Biome::Forest => boulder(random),
+            Biome::Plains if roll < 0.6 => tree(random),
+            Biome::Plains if roll < 0.9 => boulder(random),
+            Biome::Plains => ruin(random),
+            Biome::Desert if roll < 0.7 => ruin(random),
+            Biome::Desert => boulder(random),
+            Biome::Tundra if roll < 0.5 => tree(random),
+            Biome::Tundra => boulder(random),
+        }
+    }
+
+    /// The blocks of the structure relative to its origin, which is the air block right above the surface
+    pub fn blocks(
+        &self,
+        generation_blocks: &GenerationBlocks,
+    ) -> Vec<(cgmath::Vector3<i32>, Block)> {
+        let mut blocks = vec![];
+        match *self {
+            Structure::Tree { trunk_height } => {
+                for y in 0..trunk_height {
+                    blocks.push((cgmath::vec3(0, y, 0), generation_blocks.log));
+                }
+                for y in trunk_height - 2..=trunk_height + 1 {
+                    let radius: i32 = if y < trunk_height { 2 } else { 1 };
+                    for x in -radius..=radius {
+                        for z in -radius..=radius {
+                            // round off the corners of the canopy
+                            if x.abs() == radius && z.abs() == radius {
+                                continue;
+                            }
+                            blocks.push((cgmath::vec3(x, y, z), generation_blocks.leaves));
+                        }
+                    }
+                }
+            }
+
+            Structure::Boulder { radius } => {
+                for x in -radius..=radius {
+                    for y in -radius..=radius {
+                        for z in -radius..=radius {
+                            if x * x + y * y + z * z <= radius * radius + 1 {
+                                blocks.push((cgmath::vec3(x, y, z), generation_blocks.stone));
+                            }
+                        }
+                    }
+                }
+            }
+
+            Structure::Ruin { size, seed } => {
+                let mut random = Random::new(seed);
+                let half = size / 2;
+                for x in -half..=half {
+                    for z in -half..=half {
+                        let is_wall = x.abs() == half || z.abs() == half;
+                        let is_entrance = x == 0 && z == -half;
+                        if is_wall && !is_entrance {
+                            // the walls have crumbled to different heights
+                            for y in 0..random.range(0..=3) {
+                                blocks.push((cgmath::vec3(x, y, z), generation_blocks.cobblestone));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        blocks
+    }
+}
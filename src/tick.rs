@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, HashSet};
+
+/// How many world ticks run every second, no matter the frame rate
+pub const TICKS_PER_SECOND: u32 = 20;
+
+/// Keeps track of the world time and the block updates that were scheduled for later ticks.
+///
+/// Updates that are due on the same tick run in the order they were scheduled in,
+/// so the simulation is the same every time it runs
+#[derive(Default)]
+pub struct TickScheduler {
+    tick: u64,
+    scheduled: BTreeMap<u64, Vec<cgmath::Vector3<i32>>>,
+    /// The same block is only updated once per tick, even if it is scheduled more than once
+    pending: HashSet<(u64, cgmath::Vector3<i32>)>,
+}
+
+impl TickScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of ticks that have run so far
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Schedules an update of the block `delay` ticks from now, a delay of 0 is treated as 1
+    pub fn schedule(&mut self, position: cgmath::Vector3<i32>, delay: u32) {
+        self.schedule_at(self.tick + delay.max(1) as u64, position);
+    }
+
+    fn schedule_at(&mut self, tick: u64, position: cgmath::Vector3<i32>) {
+        if self.pending.insert((tick, position)) {
+            self.scheduled.entry(tick).or_default().push(position);
+        }
+    }
+
+    /// Moves on to the next tick and returns the blocks that are due to be updated on it
+    pub fn advance(&mut self) -> Vec<cgmath::Vector3<i32>> {
+        self.tick += 1;
+        let due = self.scheduled.remove(&self.tick).unwrap_or_default();
+        for &position in &due {
+            self.pending.remove(&(self.tick, position));
+        }
+        due
+    }
+
+    /// Every scheduled update as `(tick, position)` pairs, in the order they will run
+    pub fn scheduled(&self) -> impl Iterator<Item = (u64, cgmath::Vector3<i32>)> + '_ {
+        self.scheduled
+            .iter()
+            .flat_map(|(&tick, positions)| positions.iter().map(move |&position| (tick, position)))
+    }
+
+    /// Restores a scheduler from [`TickScheduler::tick`] and [`TickScheduler::scheduled`],
+    /// updates that are scheduled in the past run on the next tick
+    pub fn restore(
+        tick: u64,
+        scheduled: impl IntoIterator<Item = (u64, cgmath::Vector3<i32>)>,
+    ) -> Self {
+        let mut scheduler = Self {
+            tick,
+            ..Self::default()
+        };
+        for (scheduled_tick, position) in scheduled {
+            scheduler.schedule_at(scheduled_tick.max(tick + 1), position);
+        }
+        scheduler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_run_once_when_they_are_due() {
+        let mut scheduler = TickScheduler::new();
+        let a = cgmath::vec3(1, 2, 3);
+        let b = cgmath::vec3(-4, 5, 6);
+        scheduler.schedule(a, 2);
+        scheduler.schedule(b, 2);
+        scheduler.schedule(a, 2);
+        scheduler.schedule(b, 0);
+
+        assert_eq!(scheduler.advance(), vec![b]);
+        assert_eq!(scheduler.advance(), vec![a, b]);
+        assert!(scheduler.advance().is_empty());
+        assert_eq!(scheduler.tick(), 3);
+
+        scheduler.schedule(a, 5);
+        let mut restored = TickScheduler::restore(scheduler.tick(), scheduler.scheduled());
+        for _ in 0..4 {
+            assert!(restored.advance().is_empty());
+        }
+        assert_eq!(restored.advance(), vec![a]);
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread::JoinHandle,
+};
+
+type Job<T> = Box<dyn FnOnce() -> T + Send>;
+
+struct Queue<K, T> {
+    /// Jobs that haven't started yet, with their priority
+    jobs: Vec<(u64, K, Job<T>)>,
+    /// The number of jobs that have started but haven't finished yet
+    running: usize,
+    shutting_down: bool,
+}
+
+struct Shared<K, T> {
+    queue: Mutex<Queue<K, T>>,
+    job_added: Condvar,
+    job_finished: Condvar,
+}
+
+/// Runs jobs on background threads and sends their results back to the thread that owns the pool.
+///
+/// Each job has a key that identifies it, like the position of the chunk it works on, and a priority where
+/// lower values run first. Jobs that haven't started yet can still be cancelled or given a different priority,
+/// but jobs that are already running always finish and send their result
+pub struct WorkerPool<K, T> {
+    shared: Arc<Shared<K, T>>,
+    results: mpsc::Receiver<(K, T)>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl<K: Send + 'static, T: Send + 'static> WorkerPool<K, T> {
+    pub fn new(thread_count: usize) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                jobs: vec![],
+                running: 0,
+                shutting_down: false,
+            }),
+            job_added: Condvar::new(),
+            job_finished: Condvar::new(),
+        });
+        let (sender, results) = mpsc::channel();
+        let threads = (0..thread_count.max(1))
+            .map(|index| {
+                let shared = Arc::clone(&shared);
+                let sender = sender.clone();
+                std::thread::Builder::new()
+                    .name(format!("worker {index}"))
+                    .spawn(move || work(&shared, &sender))
+                    .expect("failed to spawn a worker thread")
+            })
+            .collect();
+        Self {
+            shared,
+            results,
+            threads,
+        }
+    }
+
+    /// The number of jobs that haven't started yet
+    pub fn queued(&self) -> usize {
+        self.shared.queue.lock().unwrap().jobs.len()
+    }
+
+    pub fn submit(&self, key: K, priority: u64, job: impl FnOnce() -> T + Send + 'static) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.jobs.push((priority, key, Box::new(job)));
+        self.shared.job_added.notify_one();
+    }
+
+    /// Removes the jobs that haven't started yet and whose key matches
+    pub fn cancel(&self, mut matches: impl FnMut(&K) -> bool) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.jobs.retain(|(_, key, _)| !matches(key));
+    }
+
+    /// Gives every job that hasn't started yet a new priority
+    pub fn reprioritize(&self, mut priority: impl FnMut(&K) -> u64) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        for (job_priority, key, _) in &mut queue.jobs {
+            *job_priority = priority(key);
+        }
+    }
+
+    /// Blocks until every job has finished, including the ones that haven't started yet.
+    /// Their results can be received with [`WorkerPool::finished`] afterwards
+    pub fn wait(&self) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        while !queue.jobs.is_empty() || queue.running > 0 {
+            queue = self.shared.job_finished.wait(queue).unwrap();
+        }
+    }
+
+    /// The results of the jobs that have finished since the last call, this doesn't wait for any jobs
+    pub fn finished(&self) -> impl Iterator<Item = (K, T)> + '_ {
+        self.results.try_iter()
+    }
+}
+
+fn work<K, T>(shared: &Shared<K, T>, sender: &mpsc::Sender<(K, T)>) {
+    loop {
+        let (key, job) = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if queue.shutting_down {
+                    return;
+                }
+                // the queue is short enough that searching it is faster than keeping it sorted
+                // while priorities change
+                let next = (0..queue.jobs.len()).min_by_key(|&index| queue.jobs[index].0);
+                if let Some(index) = next {
+                    let (_, key, job) = queue.jobs.swap_remove(index);
+                    queue.running += 1;
+                    break (key, job);
+                }
+                queue = shared.job_added.wait(queue).unwrap();
+            }
+        };
+        let sent = sender.send((key, job()));
+        // the result is sent first so it can be received as soon as waiting for the job is over
+        shared.queue.lock().unwrap().running -= 1;
+        shared.job_finished.notify_all();
+        if sent.is_err() {
+            return;
+        }
+    }
+}
+
+impl<K, T> Drop for WorkerPool<K, T> {
+    /// Waits for the running jobs to finish, the jobs that haven't started yet are dropped
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().shutting_down = true;
+        self.shared.job_added.notify_all();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jobs_run_in_priority_order_unless_cancelled() {
+        let pool = WorkerPool::new(1);
+        // keeps the only worker busy until all the other jobs are queued
+        let (unblock, blocked) = mpsc::channel::<()>();
+        pool.submit(0, 0, move || {
+            blocked.recv().unwrap();
+            0
+        });
+        while pool.queued() > 0 {
+            std::thread::yield_now();
+        }
+
+        for key in 1..=5 {
+            pool.submit(key, 10 - key as u64, move || key * 10);
+        }
+        pool.cancel(|&key| key == 4);
+        pool.reprioritize(|&key| if key == 1 { 0 } else { key as u64 });
+        unblock.send(()).unwrap();
+
+        let mut results = vec![];
+        while results.len() < 5 {
+            results.extend(pool.finished());
+            std::thread::yield_now();
+        }
+        assert_eq!(results, [(0, 0), (1, 10), (2, 20), (3, 30), (5, 50)]);
+    }
+
+    #[test]
+    fn waiting_finishes_every_job() {
+        let pool = WorkerPool::new(2);
+        for key in 0..20 {
+            pool.submit(key, key as u64, move || {
+                std::thread::sleep(std::time::Duration::from_millis(1));
+                key * 10
+            });
+        }
+        pool.wait();
+        let mut results = pool.finished().collect::<Vec<_>>();
+        results.sort();
+        assert_eq!(
+            results,
+            (0..20).map(|key| (key, key * 10)).collect::<Vec<_>>()
+        );
+    }
+}
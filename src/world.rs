@@ -0,0 +1,1008 @@
+use crate::{
+    behavior::{self, BlockChange, Update},
+    biome::Biome,
+    chunk::{Block, Chunk, Faces, MeshingMode, CHUNK_SIZE},
+    entity::{Fall, FallingBlock},
+    fluid,
+    generation::{GenerationBlocks, TerrainGenerator},
+    light::{self, Light},
+    noise::{self, Random},
+    region::{self, ByteReader, Region},
+    registry::BlockRegistry,
+    state::BlockState,
+    structure::Structure,
+    tick::TickScheduler,
+};
+use anyhow::{bail, Context};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Arc,
+};
+
+const LEVEL_FILE_NAME: &str = "level.dat";
+const REGIONS_DIRECTORY_NAME: &str = "regions";
+const LEVEL_MAGIC: [u8; 4] = *b"BGLV";
+/// Bump this whenever the layout of the level file changes
+pub const LEVEL_FORMAT_VERSION: u32 = 4;
+
+/// The offsets to the six blocks that share a face with a block
+pub const DIRECTIONS: [cgmath::Vector3<i32>; 6] = [
+    cgmath::Vector3::new(1, 0, 0),
+    cgmath::Vector3::new(-1, 0, 0),
+    cgmath::Vector3::new(0, 1, 0),
+    cgmath::Vector3::new(0, -1, 0),
+    cgmath::Vector3::new(0, 0, 1),
+    cgmath::Vector3::new(0, 0, -1),
+];
+pub const DOWN: cgmath::Vector3<i32> = cgmath::Vector3::new(0, -1, 0);
+pub const UP: cgmath::Vector3<i32> = cgmath::Vector3::new(0, 1, 0);
+
+/// Splits a world space block position into the position of the chunk that contains it
+/// and the position of the block inside that chunk
+pub fn split_block_position(
+    position: cgmath::Vector3<i32>,
+) -> (cgmath::Vector3<i32>, cgmath::Vector3<u8>) {
+    let size = CHUNK_SIZE as i32;
+    (
+        position.map(|x| x.div_euclid(size)),
+        position.map(|x| x.rem_euclid(size) as u8),
+    )
+}
+
+pub fn chunk_origin(chunk_position: cgmath::Vector3<i32>) -> cgmath::Vector3<i32> {
+    chunk_position * CHUNK_SIZE as i32
+}
+
+/// A chunk that was read from a save or generated by a [`ChunkSource`], and still has to be added to the world
+/// with [`World::add_chunk`]
+pub enum SourcedChunk {
+    Loaded(Chunk),
+    /// Only the terrain, the structures that start in the chunk are placed when it is added
+    Generated(Chunk),
+}
+
+/// Reads and generates chunks without access to the world, so it can be done on other threads
+#[derive(Clone)]
+pub struct ChunkSource {
+    registry: Arc<BlockRegistry>,
+    generator: Arc<TerrainGenerator>,
+}
+
+impl ChunkSource {
+    /// Reads the chunk from the region files inside of `directory`, or generates it if it was never saved
+    pub fn read_or_generate(
+        &self,
+        directory: &Path,
+        chunk_position: cgmath::Vector3<i32>,
+    ) -> anyhow::Result<SourcedChunk> {
+        let (region_position, index) = region::split_chunk_position(chunk_position);
+        let path = region::region_path(&directory.join(REGIONS_DIRECTORY_NAME), region_position);
+        let chunk = Region::read(&path)?
+            .get_chunk(index, &self.registry)
+            .with_context(|| format!("reading {}", path.display()))?;
+        Ok(match chunk {
+            Some(chunk) => SourcedChunk::Loaded(chunk),
+            None => SourcedChunk::Generated(self.generator.generate_chunk(chunk_position)),
+        })
+    }
+}
+
+/// A copy of a chunk and of the blocks around it, so its faces can be generated on another thread
+pub struct ChunkSnapshot {
+    pub chunk: Chunk,
+    /// The blocks in the layer around the chunk, indexed by [`ChunkSnapshot::border_index`].
+    /// The blocks inside of the chunk are left out
+    border: Vec<Option<(Block, BlockState, Light, u8)>>,
+}
+
+impl ChunkSnapshot {
+    const SIZE: i32 = CHUNK_SIZE as i32 + 2;
+
+    fn border_index(local: cgmath::Vector3<i32>) -> Option<usize> {
+        let range = -1..Self::SIZE - 1;
+        (range.contains(&local.x) && range.contains(&local.y) && range.contains(&local.z)).then(
+            || (((local.x + 1) * Self::SIZE + local.y + 1) * Self::SIZE + local.z + 1) as usize,
+        )
+    }
+
+    /// The same as [`World::generate_chunk_faces`] at the time the snapshot was taken
+    pub fn generate_faces(&self, registry: &BlockRegistry, meshing_mode: MeshingMode) -> Faces {
+        self.chunk.generate_faces(registry, meshing_mode, |local| {
+            self.border[Self::border_index(local)?]
+        })
+    }
+}
+
+pub struct World {
+    registry: Arc<BlockRegistry>,
+    generator: Arc<TerrainGenerator>,
+    chunks: HashMap<cgmath::Vector3<i32>, Chunk>,
+    /// Structure blocks that were placed into chunks that have not been generated yet
+    pending_structure_blocks: HashMap<cgmath::Vector3<i32>, Vec<(cgmath::Vector3<u8>, Block)>>,
+    ticks: TickScheduler,
+    falling_blocks: Vec<FallingBlock>,
+}
+
+impl World {
+    pub fn new(seed: u64, registry: BlockRegistry) -> anyhow::Result<Self> {
+        Ok(Self {
+            generator: Arc::new(TerrainGenerator::new(
+                seed,
+                GenerationBlocks::resolve(&registry)?,
+            )),
+            registry: Arc::new(registry),
+            chunks: HashMap::new(),
+            pending_structure_blocks: HashMap::new(),
+            ticks: TickScheduler::new(),
+            falling_blocks: vec![],
+        })
+    }
+
+    pub fn registry(&self) -> &BlockRegistry {
+        &self.registry
+    }
+
+    pub fn generator(&self) -> &TerrainGenerator {
+        &self.generator
+    }
+
+    pub fn chunk_source(&self) -> ChunkSource {
+        ChunkSource {
+            registry: Arc::clone(&self.registry),
+            generator: Arc::clone(&self.generator),
+        }
+    }
+
+    /// Shares the registry with other threads, like the ones that mesh [`ChunkSnapshot`]s
+    pub fn shared_registry(&self) -> Arc<BlockRegistry> {
+        Arc::clone(&self.registry)
+    }
+
+    pub fn chunk(&self, chunk_position: cgmath::Vector3<i32>) -> Option<&Chunk> {
+        self.chunks.get(&chunk_position)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = (cgmath::Vector3<i32>, &Chunk)> {
+        self.chunks
+            .iter()
+            .map(|(&position, chunk)| (position, chunk))
+    }
+
+    /// Lights the chunk and updates the light of its neighbors
+    pub fn insert_chunk(
+        &mut self,
+        chunk_position: cgmath::Vector3<i32>,
+        chunk: Chunk,
+    ) -> Option<Chunk> {
+        // the light from the old chunk has to be removed from its neighbors first
+        let old = self.remove_chunk(chunk_position);
+        self.chunks.insert(chunk_position, chunk);
+        light::light_chunk(&mut self.chunks, &self.registry, chunk_position);
+        old
+    }
+
+    /// Removes the light that spread from the chunk into its neighbors
+    pub fn remove_chunk(&mut self, chunk_position: cgmath::Vector3<i32>) -> Option<Chunk> {
+        let chunk = self.chunks.remove(&chunk_position)?;
+        light::unlight_chunk(&mut self.chunks, &self.registry, chunk_position);
+        Some(chunk)
+    }
+
+    /// Generates a chunk and the structures that start inside of it, replacing the chunk if it was already loaded.
+    ///
+    /// The resulting blocks are the same no matter what order chunks are generated in
+    pub fn generate_chunk(&mut self, chunk_position: cgmath::Vector3<i32>) {
+        let chunk = self.generator.generate_chunk(chunk_position);
+        self.add_chunk(chunk_position, SourcedChunk::Generated(chunk));
+    }
+
+    /// Inserts a chunk from a [`ChunkSource`] along with the structure blocks that were waiting for it,
+    /// and places the structures that start in it if it was just generated
+    pub fn add_chunk(&mut self, chunk_position: cgmath::Vector3<i32>, chunk: SourcedChunk) {
+        let (mut chunk, generated) = match chunk {
+            SourcedChunk::Loaded(chunk) => (chunk, false),
+            SourcedChunk::Generated(chunk) => (chunk, true),
+        };
+        for (local, block) in self
+            .pending_structure_blocks
+            .remove(&chunk_position)
+            .unwrap_or_default()
+        {
+            merge_structure_block(&self.registry, &mut chunk, local, block);
+        }
+        chunk.compact();
+        self.insert_chunk(chunk_position, chunk);
+
+        if generated {
+            for (origin, structure) in self.generator.structures(chunk_position) {
+                self.place_structure(origin, &structure);
+            }
+        }
+    }
+
+    /// Writes the blocks of a structure into the world, blocks in chunks that are not loaded
+    /// are queued up until that chunk is generated
+    pub fn place_structure(&mut self, origin: cgmath::Vector3<i32>, structure: &Structure) {
+        let mut changed = vec![];
+        for (offset, block) in structure.blocks(self.generator.blocks()) {
+            let position = origin + offset;
+            let (chunk_position, local) = split_block_position(position);
+            match self.chunks.get_mut(&chunk_position) {
+                Some(chunk) => {
+                    if merge_structure_block(&self.registry, chunk, local, block) {
+                        changed.push(position);
+                    }
+                }
+                None => self
+                    .pending_structure_blocks
+                    .entry(chunk_position)
+                    .or_default()
+                    .push((local, block)),
+            }
+        }
+        light::relight_blocks(&mut self.chunks, &self.registry, &changed);
+    }
+
+    /// Whether [`World::save`] has been called with this directory before
+    pub fn save_exists(directory: impl AsRef<Path>) -> bool {
+        directory.as_ref().join(LEVEL_FILE_NAME).is_file()
+    }
+
+    /// Saves all the loaded chunks into region files inside of `directory`, along with the seed,
+    /// the structure blocks that are still waiting for their chunk to be generated, the scheduled block updates
+    /// and the falling blocks.
+    ///
+    /// Chunks that were saved before but are not loaded anymore are kept
+    pub fn save(&self, directory: impl AsRef<Path>) -> anyhow::Result<()> {
+        let directory = directory.as_ref();
+        save_chunks(
+            directory,
+            self.chunks
+                .iter()
+                .map(|(&chunk_position, chunk)| (chunk_position, chunk)),
+        )?;
+
+        let mut level = vec![];
+        level.extend_from_slice(&LEVEL_MAGIC);
+        level.extend_from_slice(&LEVEL_FORMAT_VERSION.to_le_bytes());
+        level.extend_from_slice(&self.generator.seed().to_le_bytes());
+        level.extend_from_slice(&(self.pending_structure_blocks.len() as u32).to_le_bytes());
+        for (chunk_position, blocks) in &self.pending_structure_blocks {
+            for coordinate in [chunk_position.x, chunk_position.y, chunk_position.z] {
+                level.extend_from_slice(&coordinate.to_le_bytes());
+            }
+            level.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+            for (local, block) in blocks {
+                level.extend_from_slice(&[local.x, local.y, local.z]);
+                level.extend_from_slice(&block.0.to_le_bytes());
+            }
+        }
+        level.extend_from_slice(&self.ticks.tick().to_le_bytes());
+        level.extend_from_slice(&(self.ticks.scheduled().count() as u32).to_le_bytes());
+        for (tick, position) in self.ticks.scheduled() {
+            level.extend_from_slice(&tick.to_le_bytes());
+            for coordinate in [position.x, position.y, position.z] {
+                level.extend_from_slice(&coordinate.to_le_bytes());
+            }
+        }
+        level.extend_from_slice(&(self.falling_blocks.len() as u32).to_le_bytes());
+        for falling_block in &self.falling_blocks {
+            level.extend_from_slice(&falling_block.block.0.to_le_bytes());
+            let position = falling_block.position;
+            for value in [position.x, position.y, position.z, falling_block.velocity] {
+                level.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let path = directory.join(LEVEL_FILE_NAME);
+        let temporary_path = path.with_extension("dat.tmp");
+        std::fs::write(&temporary_path, level)
+            .with_context(|| format!("writing {}", temporary_path.display()))?;
+        std::fs::rename(&temporary_path, &path)
+            .with_context(|| format!("writing {}", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Loads a world that was written by [`World::save`] without any of its chunks,
+    /// they are loaded when they are needed with [`World::load_chunks`]
+    pub fn load(directory: impl AsRef<Path>, registry: BlockRegistry) -> anyhow::Result<Self> {
+        let directory = directory.as_ref();
+        let path = directory.join(LEVEL_FILE_NAME);
+        let level = std::fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+
+        let mut reader = ByteReader { bytes: &level };
+        if reader.take(4)? != LEVEL_MAGIC {
+            bail!("{} is not a level file", path.display());
+        }
+        let version = reader.u32()?;
+        if version != LEVEL_FORMAT_VERSION {
+            bail!("unsupported level format version {version}, expected {LEVEL_FORMAT_VERSION}");
+        }
+        let mut world = World::new(reader.u64()?, registry)?;
+        for _ in 0..reader.u32()? {
+            let chunk_position = cgmath::vec3(reader.i32()?, reader.i32()?, reader.i32()?);
+            let blocks = (0..reader.u32()?)
+                .map(|_| {
+                    let local = cgmath::vec3(reader.u8()?, reader.u8()?, reader.u8()?);
+                    if local.x as usize >= CHUNK_SIZE
+                        || local.y as usize >= CHUNK_SIZE
+                        || local.z as usize >= CHUNK_SIZE
+                    {
+                        bail!("pending structure block is outside of its chunk");
+                    }
+                    let block = Block(reader.u16()?);
+                    if !world.registry.contains(block) {
+                        bail!("unknown block id {}", block.0);
+                    }
+                    Ok((local, block))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            world
+                .pending_structure_blocks
+                .insert(chunk_position, blocks);
+        }
+        let tick = reader.u64()?;
+        let scheduled = (0..reader.u32()?)
+            .map(|_| {
+                let tick = reader.u64()?;
+                Ok((
+                    tick,
+                    cgmath::vec3(reader.i32()?, reader.i32()?, reader.i32()?),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        world.ticks = TickScheduler::restore(tick, scheduled);
+        for _ in 0..reader.u32()? {
+            let block = Block(reader.u16()?);
+            if !world.registry.contains(block) {
+                bail!("unknown block id {}", block.0);
+            }
+            let position = cgmath::vec3(reader.f32()?, reader.f32()?, reader.f32()?);
+            world.falling_blocks.push(FallingBlock {
+                block,
+                position,
+                previous_position: position,
+                velocity: reader.f32()?,
+            });
+        }
+
+        Ok(world)
+    }
+
+    /// Loads the chunks from the region files inside of `directory`, and generates the ones that were never saved.
+    /// Chunks that are already loaded are skipped, all the saved blocks must exist in the registry
+    pub fn load_chunks(
+        &mut self,
+        directory: impl AsRef<Path>,
+        chunk_positions: &[cgmath::Vector3<i32>],
+    ) -> anyhow::Result<()> {
+        let regions_directory = directory.as_ref().join(REGIONS_DIRECTORY_NAME);
+        let mut regions = HashMap::<_, Vec<_>>::new();
+        for &chunk_position in chunk_positions {
+            if !self.chunks.contains_key(&chunk_position) {
+                let (region_position, index) = region::split_chunk_position(chunk_position);
+                regions
+                    .entry(region_position)
+                    .or_default()
+                    .push((index, chunk_position));
+            }
+        }
+
+        for (region_position, chunks) in regions {
+            let path = region::region_path(&regions_directory, region_position);
+            let region = Region::read(&path)?;
+            for (index, chunk_position) in chunks {
+                match region
+                    .get_chunk(index, &self.registry)
+                    .with_context(|| format!("reading {}", path.display()))?
+                {
+                    Some(chunk) => self.add_chunk(chunk_position, SourcedChunk::Loaded(chunk)),
+                    None => self.generate_chunk(chunk_position),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the chunks from the world and returns them, so they can be written with [`save_chunks`]
+    /// on another thread. Chunks that aren't loaded are skipped
+    pub fn unload_chunks(
+        &mut self,
+        chunk_positions: &[cgmath::Vector3<i32>],
+    ) -> Vec<(cgmath::Vector3<i32>, Chunk)> {
+        chunk_positions
+            .iter()
+            .filter_map(|&chunk_position| {
+                Some((chunk_position, self.remove_chunk(chunk_position)?))
+            })
+            .collect()
+    }
+
+    /// Returns `None` if the chunk containing the block is not loaded
+    pub fn get_block(&self, position: cgmath::Vector3<i32>) -> Option<Block> {
+        let (chunk_position, local) = split_block_position(position);
+        self.chunk(chunk_position)?
+            .get_block(local.x, local.y, local.z)
+    }
+
+    /// Returns `None` if the chunk containing the block is not loaded
+    pub fn get_light(&self, position: cgmath::Vector3<i32>) -> Option<Light> {
+        let (chunk_position, local) = split_block_position(position);
+        self.chunk(chunk_position)?
+            .get_light(local.x, local.y, local.z)
+    }
+
+    /// Returns `None` if the chunk containing the block is not loaded
+    pub fn block_state(&self, position: cgmath::Vector3<i32>) -> Option<BlockState> {
+        let (chunk_position, local) = split_block_position(position);
+        self.chunk(chunk_position)?
+            .get_state(local.x, local.y, local.z)
+    }
+
+    /// Returns `None` if the chunk containing the block is not loaded
+    pub fn biome(&self, position: cgmath::Vector3<i32>) -> Option<Biome> {
+        let (chunk_position, local) = split_block_position(position);
+        Some(self.chunk(chunk_position)?.biome(local.x, local.z))
+    }
+
+    pub fn falling_blocks(&self) -> &[FallingBlock] {
+        &self.falling_blocks
+    }
+
+    /// Returns `None` if the chunk containing the block is not loaded
+    pub fn fluid_level(&self, position: cgmath::Vector3<i32>) -> Option<u8> {
+        let (chunk_position, local) = split_block_position(position);
+        self.chunk(chunk_position)?
+            .get_fluid_level(local.x, local.y, local.z)
+    }
+
+    /// Updates the light and fluids around the block. Fluids are placed as sources.
+    /// Nothing changes if the chunk containing the block is not loaded.
+    ///
+    /// Returns the positions of the blocks that look different now, see [`World::tick`]
+    pub fn set_block(
+        &mut self,
+        position: cgmath::Vector3<i32>,
+        block: Block,
+    ) -> Vec<cgmath::Vector3<i32>> {
+        let (chunk_position, _) = split_block_position(position);
+        if !self.chunks.contains_key(&chunk_position) {
+            return vec![];
+        }
+        let level = match self.registry.fluid(block) {
+            Some(_) => fluid::SOURCE_LEVEL,
+            None => 0,
+        };
+        self.apply_changes(&[(position, block, level)])
+    }
+
+    /// Like [`World::set_block`], but the block is placed with a state instead of the default one.
+    /// Use [`BlockState::placed`] to turn it the way the player is looking
+    pub fn place_block(
+        &mut self,
+        position: cgmath::Vector3<i32>,
+        block: Block,
+        state: BlockState,
+    ) -> Vec<cgmath::Vector3<i32>> {
+        let changed = self.set_block(position, block);
+        let (chunk_position, local) = split_block_position(position);
+        if let Some(chunk) = self.chunks.get_mut(&chunk_position) {
+            chunk.set_state(local.x, local.y, local.z, state);
+        }
+        changed
+    }
+
+    /// Changes the state of a block without replacing it, like opening a door.
+    /// Returns the positions of the blocks that look different now, see [`World::tick`]
+    pub fn set_block_state(
+        &mut self,
+        position: cgmath::Vector3<i32>,
+        state: BlockState,
+    ) -> Vec<cgmath::Vector3<i32>> {
+        let (chunk_position, local) = split_block_position(position);
+        let Some(chunk) = self.chunks.get_mut(&chunk_position) else {
+            return vec![];
+        };
+        if chunk.get_state(local.x, local.y, local.z) == Some(state) {
+            return vec![];
+        }
+        chunk.set_state(local.x, local.y, local.z, state);
+        self.update_around(vec![position])
+    }
+
+    /// Sets the blocks and their fluid levels, then updates the light and schedules fluid updates around them.
+    /// The blocks are reset to the default state. The chunks must be loaded.
+    ///
+    /// Returns the positions of the blocks whose block, fluid level or light changed
+    fn apply_changes(
+        &mut self,
+        changes: &[(cgmath::Vector3<i32>, Block, u8)],
+    ) -> Vec<cgmath::Vector3<i32>> {
+        for &(position, block, level) in changes {
+            let (chunk_position, local) = split_block_position(position);
+            let chunk = self.chunks.get_mut(&chunk_position).unwrap();
+            chunk.set_block(local.x, local.y, local.z, block);
+            chunk.set_fluid_level(local.x, local.y, local.z, level);
+            chunk.set_state(local.x, local.y, local.z, BlockState::default());
+        }
+        self.update_around(changes.iter().map(|&(position, ..)| position).collect())
+    }
+
+    /// Updates the light and schedules block updates around blocks that have changed, which must be in loaded
+    /// chunks.
+    ///
+    /// Returns the positions together with the positions whose light changed
+    fn update_around(&mut self, positions: Vec<cgmath::Vector3<i32>>) -> Vec<cgmath::Vector3<i32>> {
+        let relit = light::relight_blocks(&mut self.chunks, &self.registry, &positions);
+
+        for &position in &positions {
+            for neighbor in std::iter::once(position).chain(DIRECTIONS.map(|d| position + d)) {
+                if let Some(delay) = behavior::tick_delay(self, neighbor) {
+                    self.ticks.schedule(neighbor, delay);
+                }
+            }
+        }
+        positions.into_iter().chain(relit).collect()
+    }
+
+    /// Runs the block updates that are due on the next tick and the random ticks, this should be called
+    /// [`TICKS_PER_SECOND`](crate::tick::TICKS_PER_SECOND) times a second.
+    ///
+    /// Returns the positions of the blocks whose block, fluid level or light changed,
+    /// which can contain duplicates
+    pub fn tick(&mut self) -> Vec<cgmath::Vector3<i32>> {
+        let mut updates = self
+            .ticks
+            .advance()
+            .into_iter()
+            .map(|position| (Some(position), behavior::scheduled_update(self, position)))
+            .collect::<Vec<_>>();
+
+        let mut chunk_positions = self
+            .chunks
+            .iter()
+            .filter(|(_, chunk)| {
+                chunk
+                    .blocks
+                    .palette()
+                    .iter()
+                    .any(|&block| self.registry.get(block).behavior.ticks_randomly())
+            })
+            .map(|(&chunk_position, _)| chunk_position)
+            .collect::<Vec<_>>();
+        // sorted so the updates happen in the same order every time
+        chunk_positions.sort_by_key(|position| (position.x, position.y, position.z));
+        for chunk_position in chunk_positions {
+            let mut random = Random::new(noise::hash(
+                self.generator.seed(),
+                &[
+                    self.ticks.tick() as i64,
+                    chunk_position.x as i64,
+                    chunk_position.y as i64,
+                    chunk_position.z as i64,
+                ],
+            ));
+            for _ in 0..behavior::RANDOM_TICKS_PER_CHUNK {
+                let max = CHUNK_SIZE as i32 - 1;
+                let local = cgmath::vec3(
+                    random.range(0..=max),
+                    random.range(0..=max),
+                    random.range(0..=max),
+                );
+                let position = chunk_origin(chunk_position) + local;
+                let changes = behavior::random_update(self, position, &mut random);
+                updates.push((
+                    None,
+                    Update {
+                        changes,
+                        falling_blocks: vec![],
+                    },
+                ));
+            }
+        }
+
+        // every update sees the world as it was at the start of the tick, so when two of them
+        // change the same block only the first one happens and the other one is tried again later
+        let mut changed = HashSet::new();
+        let mut changes = vec![];
+        let mut started_falling = vec![];
+        for (scheduled, update) in updates {
+            if update
+                .changes
+                .iter()
+                .any(|(position, ..)| changed.contains(position))
+            {
+                if let Some(position) = scheduled {
+                    self.ticks.schedule(position, 1);
+                }
+                continue;
+            }
+            changed.extend(update.changes.iter().map(|&(position, ..)| position));
+            changes.extend(update.changes);
+            started_falling.extend(update.falling_blocks);
+        }
+        let mut changed = self.apply_changes(&changes);
+
+        // the blocks that just started falling start moving on the next tick
+        let mut falling_blocks = std::mem::take(&mut self.falling_blocks);
+        let mut landed: Vec<BlockChange> = vec![];
+        falling_blocks.retain_mut(|falling_block| {
+            let Fall::Landed(mut position) = falling_block.step(self) else {
+                return true;
+            };
+            // falling blocks go through each other, so the ones that land in the same place on the same tick
+            // stack up on top of each other, and on top of solid blocks that were placed there on this tick
+            loop {
+                if landed.iter().any(|&(other, ..)| other == position) {
+                    position += UP;
+                    continue;
+                }
+                match self.get_block(position) {
+                    Some(block) if block == Block::AIR || self.registry.fluid(block).is_some() => {
+                        landed.push((position, falling_block.block, 0));
+                        break;
+                    }
+                    Some(block) if self.registry.get(block).solid => position += UP,
+                    // it breaks if it lands inside of a block that isn't solid, like a plant, or if there is no
+                    // room for it in the loaded chunks
+                    _ => break,
+                }
+            }
+            false
+        });
+        self.falling_blocks = falling_blocks;
+        self.falling_blocks.extend(started_falling);
+        changed.extend(self.apply_changes(&landed));
+        changed
+    }
+
+    /// What meshing needs to know about a block: the block, its state, its light and its fluid level.
+    /// Returns `None` if the chunk containing the block is not loaded
+    fn block_with_light(
+        &self,
+        position: cgmath::Vector3<i32>,
+    ) -> Option<(Block, BlockState, Light, u8)> {
+        let (chunk_position, local) = split_block_position(position);
+        let chunk = self.chunk(chunk_position)?;
+        Some((
+            chunk.get_block(local.x, local.y, local.z)?,
+            chunk.get_state(local.x, local.y, local.z)?,
+            chunk.get_light(local.x, local.y, local.z)?,
+            chunk.get_fluid_level(local.x, local.y, local.z)?,
+        ))
+    }
+
+    /// Copies the chunk and the blocks around it, returns `None` if the chunk isn't loaded
+    pub fn snapshot_chunk(&self, chunk_position: cgmath::Vector3<i32>) -> Option<ChunkSnapshot> {
+        let chunk = self.chunk(chunk_position)?.clone();
+        let origin = chunk_origin(chunk_position);
+        let size = ChunkSnapshot::SIZE;
+        let mut border = vec![None; (size * size * size) as usize];
+        for x in -1..size - 1 {
+            for y in -1..size - 1 {
+                for z in -1..size - 1 {
+                    let local = cgmath::vec3(x, y, z);
+                    if [x, y, z]
+                        .iter()
+                        .all(|&c| (0..CHUNK_SIZE as i32).contains(&c))
+                    {
+                        continue;
+                    }
+                    border[ChunkSnapshot::border_index(local).unwrap()] =
+                        self.block_with_light(origin + local);
+                }
+            }
+        }
+        Some(ChunkSnapshot { chunk, border })
+    }
+
+    /// Generates the faces of a chunk, culling faces against the neighboring chunks
+    pub fn generate_chunk_faces(
+        &self,
+        chunk_position: cgmath::Vector3<i32>,
+        meshing_mode: MeshingMode,
+    ) -> Option<Faces> {
+        let chunk = self.chunk(chunk_position)?;
+        let origin = chunk_origin(chunk_position);
+        Some(chunk.generate_faces(&self.registry, meshing_mode, |local| {
+            self.block_with_light(origin + local)
+        }))
+    }
+}
+
+#[cfg(test)]
+impl World {
+    /// A world with the default registry and a single chunk at the origin, whose bottom layer is `floor`
+    pub(crate) fn test_floor(floor: &str) -> Self {
+        let mut world = World::new(0, crate::registry::default_registry()).unwrap();
+        let floor = world.registry().by_name(floor).unwrap();
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_SIZE as u8 {
+            for z in 0..CHUNK_SIZE as u8 {
+                chunk.set_block(x, 0, z, floor);
+            }
+        }
+        world.insert_chunk(cgmath::vec3(0, 0, 0), chunk);
+        world
+    }
+
+    pub(crate) fn run_ticks(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+}
+
+/// Writes the chunks into the region files inside of `directory`, replacing the chunks that were saved there before.
+///
+/// Each region file is read and written again as a whole, so two saves must not run at the same time
+pub fn save_chunks<'a>(
+    directory: &Path,
+    chunks: impl IntoIterator<Item = (cgmath::Vector3<i32>, &'a Chunk)>,
+) -> anyhow::Result<()> {
+    let regions_directory = directory.join(REGIONS_DIRECTORY_NAME);
+    std::fs::create_dir_all(&regions_directory)
+        .with_context(|| format!("creating {}", regions_directory.display()))?;
+
+    let mut regions = HashMap::<_, Vec<_>>::new();
+    for (chunk_position, chunk) in chunks {
+        let (region_position, index) = region::split_chunk_position(chunk_position);
+        regions
+            .entry(region_position)
+            .or_default()
+            .push((index, chunk));
+    }
+    for (region_position, chunks) in regions {
+        let path = region::region_path(&regions_directory, region_position);
+        let mut region = Region::read(&path)?;
+        for (index, chunk) in chunks {
+            region.set_chunk(index, chunk)?;
+        }
+        region.write(&path)?;
+    }
+    Ok(())
+}
+
+/// Returns whether the block was placed
+fn merge_structure_block(
+    registry: &BlockRegistry,
+    chunk: &mut Chunk,
+    local: cgmath::Vector3<u8>,
+    block: Block,
+) -> bool {
+    let existing = chunk.get_block(local.x, local.y, local.z).unwrap();
+    let placed = registry.placement_priority(block) > registry.placement_priority(existing);
+    if placed {
+        chunk.set_block(local.x, local.y, local.z, block);
+    }
+    placed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::default_registry;
+
+    #[test]
+    fn structures_dont_depend_on_generation_order() {
+        let mut chunk_positions = vec![];
+        for x in -2..2 {
+            for y in -1..1 {
+                for z in -2..2 {
+                    chunk_positions.push(cgmath::vec3(x, y, z));
+                }
+            }
+        }
+
+        let mut forwards = World::new(5, default_registry()).unwrap();
+        for &chunk_position in &chunk_positions {
+            forwards.generate_chunk(chunk_position);
+        }
+        let mut backwards = World::new(5, default_registry()).unwrap();
+        for &chunk_position in chunk_positions.iter().rev() {
+            backwards.generate_chunk(chunk_position);
+        }
+
+        let mut structure_blocks = 0;
+        for &chunk_position in &chunk_positions {
+            let a = forwards.chunk(chunk_position).unwrap();
+            let b = backwards.chunk(chunk_position).unwrap();
+            assert!(a.blocks == b.blocks);
+            let blocks = forwards.generator().blocks();
+            structure_blocks += a
+                .blocks
+                .iter()
+                .filter(|&block| [blocks.log, blocks.leaves, blocks.cobblestone].contains(&block))
+                .count();
+        }
+        assert!(structure_blocks > 0);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() -> anyhow::Result<()> {
+        let directory = std::env::temp_dir().join(format!(
+            "block_game_save_and_load_round_trip_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let mut world = World::new(9, default_registry())?;
+        let cobblestone = world.generator().blocks().cobblestone;
+        for x in -1..1 {
+            for z in -1..1 {
+                world.generate_chunk(cgmath::vec3(x, 0, z));
+            }
+        }
+        // above the generated chunks
+        world.insert_chunk(cgmath::vec3(0, 2, -1), Chunk::new());
+        world.set_block(cgmath::vec3(3, 40, -7), cobblestone);
+        let water = world.registry().by_name("water")?;
+        world.set_block(cgmath::vec3(3, 41, -7), water);
+        let furnace = world.registry().by_name("furnace")?;
+        let furnace_state = BlockState {
+            facing: crate::state::Direction::NegativeX,
+            ..BlockState::default()
+        };
+        world.place_block(cgmath::vec3(4, 40, -7), furnace, furnace_state);
+        world.tick();
+        world.save(&directory)?;
+
+        // saving again must keep chunks that have been unloaded since
+        let chunk_positions = world
+            .chunks()
+            .map(|(chunk_position, _)| chunk_position)
+            .collect::<Vec<_>>();
+        let removed = cgmath::vec3(0, 0, 0);
+        let mut unloaded = World::load(&directory, default_registry())?;
+        unloaded.load_chunks(&directory, &chunk_positions)?;
+        unloaded.remove_chunk(removed);
+        unloaded.save(&directory)?;
+
+        let mut loaded = World::load(&directory, default_registry())?;
+        assert_eq!(loaded.chunks().count(), 0);
+        loaded.load_chunks(&directory, &chunk_positions)?;
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(loaded.generator().seed(), 9);
+        assert_eq!(loaded.chunks().count(), world.chunks().count());
+        for (chunk_position, chunk) in world.chunks() {
+            let loaded_chunk = loaded.chunk(chunk_position).unwrap();
+            assert!(loaded_chunk.blocks == chunk.blocks);
+            assert!(loaded_chunk.states == chunk.states);
+            assert!(loaded_chunk.biomes == chunk.biomes);
+        }
+        assert_eq!(
+            loaded.pending_structure_blocks,
+            world.pending_structure_blocks
+        );
+        assert_eq!(loaded.get_block(cgmath::vec3(3, 40, -7)), Some(cobblestone));
+        assert_eq!(
+            loaded.block_state(cgmath::vec3(4, 40, -7)),
+            Some(furnace_state)
+        );
+        assert_eq!(
+            loaded.fluid_level(cgmath::vec3(3, 41, -7)),
+            Some(fluid::SOURCE_LEVEL)
+        );
+        assert_eq!(loaded.ticks.tick(), 1);
+        assert!(loaded.ticks.scheduled().eq(world.ticks.scheduled()));
+        Ok(())
+    }
+
+    #[test]
+    fn unloaded_chunks_keep_their_changes() -> anyhow::Result<()> {
+        let directory = std::env::temp_dir().join(format!(
+            "block_game_unloaded_chunks_keep_their_changes_{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let mut world = World::new(3, default_registry())?;
+        let chunk_positions = [cgmath::vec3(0, 0, 0), cgmath::vec3(1, 0, 0)];
+        world.load_chunks(&directory, &chunk_positions)?;
+        let generated = world.chunk(chunk_positions[1]).unwrap().blocks.clone();
+        let glass = world.registry().by_name("glass")?;
+        world.set_block(cgmath::vec3(20, 3, 4), glass);
+
+        let unloaded = world.unload_chunks(&chunk_positions[1..]);
+        save_chunks(
+            &directory,
+            unloaded
+                .iter()
+                .map(|(chunk_position, chunk)| (*chunk_position, chunk)),
+        )?;
+        assert!(world.chunk(chunk_positions[1]).is_none());
+        assert_eq!(world.get_block(cgmath::vec3(20, 3, 4)), None);
+        world.load_chunks(&directory, &chunk_positions)?;
+        std::fs::remove_dir_all(&directory)?;
+
+        assert_eq!(world.get_block(cgmath::vec3(20, 3, 4)), Some(glass));
+        assert!(world.chunk(chunk_positions[1]).unwrap().blocks != generated);
+        Ok(())
+    }
+
+    #[test]
+    fn snapshots_mesh_like_the_world() {
+        let mut world = World::new(5, default_registry()).unwrap();
+        for x in -1..=1 {
+            for z in 0..=1 {
+                world.generate_chunk(cgmath::vec3(x, 0, z));
+            }
+        }
+        // both chunks are missing some of their neighbors, the snapshots have to leave those out too
+        for chunk_position in [cgmath::vec3(0, 0, 0), cgmath::vec3(0, 0, 1)] {
+            let snapshot = world.snapshot_chunk(chunk_position).unwrap();
+            assert_eq!(
+                snapshot.generate_faces(world.registry(), MeshingMode::Greedy),
+                world
+                    .generate_chunk_faces(chunk_position, MeshingMode::Greedy)
+                    .unwrap()
+            );
+        }
+        assert!(world.snapshot_chunk(cgmath::vec3(0, 1, 0)).is_none());
+    }
+
+    #[test]
+    fn changing_the_state_of_a_block_updates_its_neighbors() {
+        let mut world = World::test_floor("stone");
+        let (trapdoor, sand) = (
+            world.registry().by_name("trapdoor").unwrap(),
+            world.registry().by_name("sand").unwrap(),
+        );
+        world.set_block(cgmath::vec3(3, 1, 3), trapdoor);
+        world.set_block(cgmath::vec3(3, 2, 3), sand);
+        world.run_ticks(10);
+        assert_eq!(world.ticks.scheduled().count(), 0);
+
+        let open = BlockState {
+            open: true,
+            ..BlockState::default()
+        };
+        let changed = world.set_block_state(cgmath::vec3(3, 1, 3), open);
+        assert!(changed.contains(&cgmath::vec3(3, 1, 3)));
+        assert!(world
+            .ticks
+            .scheduled()
+            .any(|(_, position)| position == cgmath::vec3(3, 2, 3)));
+        assert!(world
+            .set_block_state(cgmath::vec3(3, 1, 3), open)
+            .is_empty());
+    }
+
+    #[test]
+    fn falling_blocks_that_land_together_stack_up() {
+        let mut world = World::test_floor("stone");
+        let mut expected = World::test_floor("stone");
+        let (sand, gravel) = (
+            world.registry().by_name("sand").unwrap(),
+            world.registry().by_name("gravel").unwrap(),
+        );
+        let stone = world.registry().by_name("stone").unwrap();
+        // blocks that land where a solid block is go on top of it, unless that is outside of the loaded chunks
+        for position in [
+            cgmath::vec3(5, 1, 5),
+            cgmath::vec3(7, 14, 7),
+            cgmath::vec3(7, 15, 7),
+        ] {
+            world.set_block(position, stone);
+            expected.set_block(position, stone);
+        }
+        world.falling_blocks = vec![
+            FallingBlock::new(sand, cgmath::vec3(3, 8, 3)),
+            FallingBlock::new(gravel, cgmath::vec3(3, 8, 3)),
+            FallingBlock::new(sand, cgmath::vec3(5, 1, 5)),
+            FallingBlock::new(sand, cgmath::vec3(7, 15, 7)),
+        ];
+        world.run_ticks(50);
+
+        assert!(world.falling_blocks().is_empty());
+        expected.set_block(cgmath::vec3(3, 1, 3), sand);
+        expected.set_block(cgmath::vec3(3, 2, 3), gravel);
+        expected.set_block(cgmath::vec3(5, 2, 5), sand);
+        assert!(
+            world.chunk(cgmath::vec3(0, 0, 0)).unwrap().blocks
+                == expected.chunk(cgmath::vec3(0, 0, 0)).unwrap().blocks
+        );
+    }
+}